@@ -29,6 +29,8 @@ pub enum Error {
     Unlock(Errno),
     SupervisorError(String),
     Repl(reedline_repl_rs::Error),
+    UnixSocket(String),
+    Auth(String),
 }
 
 impl Display for Error {
@@ -63,6 +65,8 @@ impl Display for Error {
             Error::Unlock(e) => write!(f, "Error unlocking lock file : {e}"),
             Error::SupervisorError(e) => write!(f, "Supervisor error : {e}"),
             Error::Repl(e) => write!(f, "Repl error : {e}"),
+            Error::UnixSocket(e) => write!(f, "Unix socket error : {e}"),
+            Error::Auth(e) => write!(f, "Authentication error : {e}"),
         }
     }
 }
@@ -1,5 +1,6 @@
 use crate::error::{get_err, Error, Result};
 
+#[allow(dead_code)]
 pub(crate) enum ForkResult {
     Child,
     Parent(libc::pid_t),
@@ -31,7 +31,10 @@ pub fn set_sig_handlers() -> Result<()> {
             if i == libc::SIGKILL || i == libc::SIGSTOP || i == libc::SIGCHLD {
                 continue;
             }
-            get_err(libc::signal(i, handle_sig as _), Error::SignalSetting)?;
+            get_err(
+                libc::signal(i, handle_sig as *const () as libc::sighandler_t),
+                Error::SignalSetting,
+            )?;
         }
     }
     Ok(())
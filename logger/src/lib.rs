@@ -31,8 +31,7 @@ static FILE: OnceCell<String> = OnceCell::new();
 
 fn log_file() -> std::io::Result<&'static String> {
     FILE.get_or_try_init(|| {
-        std::env::var("TASKMASTER_LOGFILE")
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+        std::env::var("TASKMASTER_LOGFILE").map_err(|e| std::io::Error::other(e.to_string()))
     })
 }
 
@@ -51,7 +50,6 @@ where
     if file.file_name().is_some() {
         let mut f = fs::OpenOptions::new()
             .create(true)
-            .write(true)
             .append(true)
             .open(&file)?;
 
@@ -0,0 +1,64 @@
+use daemonize::{Error, Result};
+use std::fs;
+
+/// Read the shared token from the file in `SERVER_AUTH_TOKEN_FILE`.
+/// Clients don't need to authenticate when it is unset.
+pub(crate) fn token_from_env() -> Result<Option<String>> {
+    match std::env::var("SERVER_AUTH_TOKEN_FILE") {
+        Ok(path) => read_token(&path).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+fn read_token(path: &str) -> Result<String> {
+    let token = fs::read_to_string(path)?.trim().to_string();
+    if token.is_empty() {
+        return Err(Error::Auth(format!("The token file {path} is empty")));
+    }
+    Ok(token)
+}
+
+/// Compare the tokens without stopping at the first difference,
+/// so the time taken doesn't tell how much of the token is right
+pub(crate) fn tokens_match(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    let mut diff = expected.len() ^ given.len();
+    for (i, byte) in expected.iter().enumerate() {
+        diff |= (byte ^ given.get(i).unwrap_or(&0)) as usize;
+    }
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_tokens() {
+        assert!(tokens_match("s3cr3T", "s3cr3T"));
+        assert!(!tokens_match("s3cr3T", "s3cr3t"));
+        assert!(!tokens_match("s3cr3T", "s3cr3"));
+        assert!(!tokens_match("s3cr3T", "s3cr3T!"));
+        assert!(!tokens_match("s3cr3T", ""));
+    }
+
+    #[test]
+    fn read_token_file() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("taskmaster-{}.token", std::process::id()));
+        let path_str = path.display().to_string();
+
+        fs::write(&path, "s3cr3T\n")?;
+        assert_eq!(read_token(&path_str)?, "s3cr3T");
+
+        fs::write(&path, "  \n")?;
+        assert!(matches!(read_token(&path_str), Err(Error::Auth(_))));
+
+        // sent as a single argument of the `auth` action
+        fs::write(&path, "s3cr3T s3cr3T")?;
+        assert_eq!(read_token(&path_str)?, "s3cr3T s3cr3T");
+
+        fs::remove_file(&path)?;
+        assert!(read_token(&path_str).is_err());
+        Ok(())
+    }
+}
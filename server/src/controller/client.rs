@@ -1,19 +1,28 @@
 use daemonize::Result;
 use logger::{log, LogInfo};
 use std::{
-    io::{self, BufRead, BufReader, BufWriter, Write},
-    net::{SocketAddr, TcpStream},
-    time::Duration,
+    io::{self, BufRead, BufReader, Read, Write},
+    time::{Duration, Instant},
 };
-use supervisor::{Action, ParseActionError, Programs};
+use supervisor::{Action, Payload, Programs, Request, Response, PROTOCOL_VERSION};
 
-use crate::{Client, Clients};
+use crate::{controller::auth::tokens_match, Client, Clients, Following, Stream};
 
 const NBR_CLIENT_MAX: usize = 3;
 const READ_DURATION: Duration = Duration::from_millis(100);
+// an empty chunk is sent to a follower when nothing was written for this
+// long, so it can stop following without waiting for the output
+const FOLLOW_HEARTBEAT: Duration = Duration::from_secs(1);
 
 impl Clients {
-    pub(crate) fn add_client(&mut self, stream: TcpStream, addr: SocketAddr) -> Result<bool> {
+    pub(crate) fn new(token: Option<String>) -> Self {
+        Self {
+            clients: vec![],
+            token,
+        }
+    }
+
+    pub(crate) fn add_client(&mut self, stream: Stream, addr: String) -> Result<bool> {
         Ok(if self.clients.len() >= NBR_CLIENT_MAX {
             log(
                 format!(
@@ -24,11 +33,11 @@ impl Clients {
             )?;
             false
         } else {
-            let new_client = Client::new(stream, addr)?;
             log(
                 format!("Connecting to new client with address {}\n", addr),
                 LogInfo::Info,
             )?;
+            let new_client = Client::new(stream, addr, self.token.is_none())?;
             self.clients.push(new_client);
             true
         })
@@ -40,8 +49,13 @@ impl Clients {
     pub(crate) fn read_clients(&mut self, programs: &mut Programs) -> Result<bool> {
         let mut to_clear = vec![];
         for (i, client) in self.clients.iter_mut().enumerate() {
-            match client.read_promt(programs)? {
-                ClientResponse::Continue => (),
+            match client.read_promt(programs, self.token.as_deref())? {
+                // a follower that can't be written to is gone
+                ClientResponse::Continue => {
+                    if client.send_followed().is_err() {
+                        to_clear.push(i);
+                    }
+                }
                 ClientResponse::Disconnected => to_clear.push(i),
                 ClientResponse::Exit => return Ok(false),
             }
@@ -52,7 +66,7 @@ impl Clients {
                     "Disconnecting form client with address {:?}\n",
                     self.clients
                         .get(i)
-                        .map(|c| c.addr.to_string())
+                        .map(|c| c.addr.clone())
                         .unwrap_or_default()
                 ),
                 LogInfo::Info,
@@ -69,34 +83,168 @@ enum ClientResponse {
     Exit,
 }
 
+impl Stream {
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Stream::Tcp(s) => Stream::Tcp(s.try_clone()?),
+            Stream::Unix(s) => Stream::Unix(s.try_clone()?),
+        })
+    }
+
+    /// Accepted streams can inherit the non blocking mode of the listener
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => {
+                s.set_nonblocking(false)?;
+                s.set_read_timeout(timeout)
+            }
+            Stream::Unix(s) => {
+                s.set_nonblocking(false)?;
+                s.set_read_timeout(timeout)
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
 impl Client {
-    fn new(stream: TcpStream, addr: SocketAddr) -> Result<Self> {
+    fn new(stream: Stream, addr: String, authenticated: bool) -> Result<Self> {
         stream.set_read_timeout(Some(READ_DURATION))?;
         Ok(Self {
             reader: BufReader::new(stream.try_clone()?),
             stream,
             addr,
+            authenticated,
+            following: None,
         })
     }
 
     fn print(&mut self, buf: &[u8]) -> Result<()> {
-        let mut writer = BufWriter::new(&self.stream);
-        writer.write_all(buf)?;
+        self.stream.write_all(buf)?;
+        self.stream.flush()?;
         Ok(())
     }
 
-    /// Print the error we got trying to parse the command given
-    fn print_error(&mut self, e: ParseActionError) -> Result<()> {
-        self.print(e.to_string().as_bytes())?;
-        Ok(())
+    fn respond(&mut self, response: Response) -> Result<()> {
+        self.print(response.encode()?.as_bytes())
     }
 
-    /// Try to read from the promt.
+    /// Send what was written to the followed output since the last call
+    fn send_followed(&mut self) -> Result<()> {
+        let Some(following) = self.following.as_mut() else {
+            return Ok(());
+        };
+        let output = following.follow.read_new()?;
+        if output.is_empty() && following.last_sent.elapsed() < FOLLOW_HEARTBEAT {
+            return Ok(());
+        }
+        following.last_sent = Instant::now();
+        let id = following.id;
+        self.respond(Response::chunk(id, output))
+    }
+
+    /// Any request ends the `tail -f` of the client, with a last response
+    fn stop_following(&mut self) -> Result<()> {
+        match self.following.take() {
+            Some(following) => {
+                self.respond(Response::ok(following.id, Payload::Output(String::new())))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Answer the handshake actions, `hello` tells whether the client has to
+    /// authenticate and `auth` checks the token it sent.
+    /// Return None for any other action, which is only allowed once authenticated
+    fn authenticate(
+        &mut self,
+        id: u64,
+        action: &Action,
+        token: Option<&str>,
+    ) -> Result<Option<Response>> {
+        Ok(match (action, token) {
+            (Action::Hello, _) => Some(Response::ok(
+                id,
+                Payload::Message(
+                    if self.authenticated {
+                        "Authentication not required"
+                    } else {
+                        "Authentication required"
+                    }
+                    .to_string(),
+                ),
+            )),
+            (Action::Auth(_), None) => Some(Response::ok(
+                id,
+                Payload::Message("Authentication not required".to_string()),
+            )),
+            (Action::Auth(given), Some(expected)) => {
+                if tokens_match(expected, given) {
+                    self.authenticated = true;
+                    Some(Response::ok(
+                        id,
+                        Payload::Message("Authenticated".to_string()),
+                    ))
+                } else {
+                    log(
+                        format!("Failed authentication from client {}\n", self.addr),
+                        LogInfo::Warn,
+                    )?;
+                    Some(Response::error(id, "Authentication failed"))
+                }
+            }
+            (action, _) if !self.authenticated => {
+                log(
+                    format!(
+                        "Unauthenticated client {} tried to run {:?}\n",
+                        self.addr,
+                        action.name()
+                    ),
+                    LogInfo::Warn,
+                )?;
+                Some(Response::error(id, "Authentication required"))
+            }
+            _ => None,
+        })
+    }
+
+    /// Try to read a request from the client.
     /// Return a status corresponding of the what has been read.
     /// If nothing has been read, that means that the client has disconnected
-    /// If `exit` was read, then the client wants the server to stop
+    /// If `quit` was read, then the client wants the server to stop
     /// Otherwise, the server continues
-    fn read_promt(&mut self, programs: &mut Programs) -> Result<ClientResponse> {
+    /// Every request gets exactly one response, errors included, but `tail -f`
+    /// which is answered by `send_followed` until the next request
+    /// Nothing but the handshake is run before the client is authenticated
+    fn read_promt(
+        &mut self,
+        programs: &mut Programs,
+        token: Option<&str>,
+    ) -> Result<ClientResponse> {
         let mut buf = String::new();
         match self.reader.read_line(&mut buf) {
             Err(e) => match e.kind() {
@@ -108,19 +256,73 @@ impl Client {
                     // doesn't reach here.
                     return Ok(ClientResponse::Disconnected);
                 }
-                let action: Action = match buf.try_into() {
+                self.stop_following()?;
+                let request = match Request::decode(&buf) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.respond(Response::error(0, e))?;
+                        return Ok(ClientResponse::Continue);
+                    }
+                };
+                if request.version != PROTOCOL_VERSION {
+                    self.respond(Response::error(
+                        request.id,
+                        format!(
+                            "Unsupported protocol version {}, expected {}",
+                            request.version, PROTOCOL_VERSION
+                        ),
+                    ))?;
+                    return Ok(ClientResponse::Continue);
+                }
+                let action: Action = match request.action() {
                     Ok(a) => a,
                     Err(e) => {
-                        self.print_error(e)?;
+                        self.respond(Response::error(request.id, e.to_string().trim_end()))?;
                         return Ok(ClientResponse::Continue);
                     }
                 };
 
+                if let Some(response) = self.authenticate(request.id, &action, token)? {
+                    self.respond(response)?;
+                    return Ok(ClientResponse::Continue);
+                }
+
                 if action == Action::Quit {
+                    self.respond(Response::ok(
+                        request.id,
+                        Payload::Message("Shutting down".to_string()),
+                    ))?;
                     return Ok(ClientResponse::Exit);
                 };
 
-                self.print(programs.handle_action(action)?.as_bytes())?;
+                // the output is then streamed until the next request
+                if let Action::Tail {
+                    target,
+                    output,
+                    lines,
+                    follow: true,
+                } = &action
+                {
+                    let response = match programs.tail(target, *output, *lines) {
+                        Ok((text, follow)) => {
+                            self.following = Some(Following {
+                                id: request.id,
+                                follow,
+                                last_sent: Instant::now(),
+                            });
+                            Response::chunk(request.id, text)
+                        }
+                        Err(e) => Response::error(request.id, e),
+                    };
+                    self.respond(response)?;
+                    return Ok(ClientResponse::Continue);
+                }
+
+                let response = match programs.handle_action(action) {
+                    Ok(payload) => Response::ok(request.id, payload),
+                    Err(e) => Response::error(request.id, e),
+                };
+                self.respond(response)?;
             }
         };
         Ok(ClientResponse::Continue)
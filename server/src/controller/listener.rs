@@ -0,0 +1,214 @@
+use daemonize::{Error, Result};
+use logger::{log, LogInfo};
+use std::{
+    ffi::CString,
+    fs, io,
+    net::TcpListener,
+    os::unix::{
+        fs::{DirBuilderExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::{Listener, Stream};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:4242";
+const DEFAULT_SOCKET_MODE: &str = "0700";
+
+fn user_id(user: &str) -> Result<libc::uid_t> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    let name = CString::new(user).map_err(|e| Error::UnixSocket(e.to_string()))?;
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    if passwd.is_null() {
+        return Err(Error::UnixSocket(format!("Unknown user {user}")));
+    }
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+fn group_id(group: &str) -> Result<libc::gid_t> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    let name = CString::new(group).map_err(|e| Error::UnixSocket(e.to_string()))?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        return Err(Error::UnixSocket(format!("Unknown group {group}")));
+    }
+    Ok(unsafe { (*entry).gr_gid })
+}
+
+fn parse_mode(mode: &str) -> Result<u32> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+        .map_err(|_| Error::UnixSocket(format!("Invalid octal mode {mode}")))
+}
+
+// `private` is in a directory only the server can enter, the socket is
+// reachable at `path` only once its owner and mode are set
+fn bind_private(
+    private: &Path,
+    path: &Path,
+    uid: Option<libc::uid_t>,
+    gid: Option<libc::gid_t>,
+    mode: u32,
+) -> Result<UnixListener> {
+    let listener = UnixListener::bind(private)?;
+    listener.set_nonblocking(true)?;
+    std::os::unix::fs::chown(private, uid, gid)?;
+    fs::set_permissions(private, fs::Permissions::from_mode(mode))?;
+    fs::rename(private, path)?;
+    Ok(listener)
+}
+
+impl Listener {
+    pub(crate) fn tcp(addr: &str) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Tcp(listener))
+    }
+
+    /// Bind a unix socket, access to it is then restricted by its owner, group and mode
+    pub(crate) fn unix(
+        path: PathBuf,
+        owner: Option<String>,
+        group: Option<String>,
+        mode: &str,
+    ) -> Result<Self> {
+        let mode = parse_mode(mode)?;
+        let uid = owner.as_deref().map(user_id).transpose()?;
+        let gid = group.as_deref().map(group_id).transpose()?;
+
+        // a socket left by a previous run that did not exit cleanly
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Err(Error::UnixSocket(format!(
+                    "{} is already used by another server",
+                    path.display()
+                )));
+            }
+            fs::remove_file(&path)?;
+        }
+        // the daemon runs with umask 0, the socket is bound in a directory
+        // only the server can enter and moved in place once restricted
+        let Some(name) = path.file_name() else {
+            return Err(Error::UnixSocket(format!(
+                "Invalid socket path {}",
+                path.display()
+            )));
+        };
+        let private = path.with_file_name(format!(
+            ".{}.{}",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        fs::DirBuilder::new().mode(0o700).create(&private)?;
+        let listener = bind_private(&private.join("socket"), &path, uid, gid, mode);
+        let _ = fs::remove_dir_all(&private);
+        Ok(Listener::Unix {
+            listener: listener?,
+            path,
+        })
+    }
+
+    /// Build the listeners from the environment:
+    /// - `SERVER_SOCKET_PATH` to listen on a unix socket, with `SERVER_SOCKET_OWNER`,
+    ///   `SERVER_SOCKET_GROUP` and `SERVER_SOCKET_MODE` (default 0700) for its permissions
+    /// - `SERVER_ADDRESS` to listen on tcp, defaults to localhost:4242 when no unix socket is set
+    pub(crate) fn from_env() -> Result<Vec<Self>> {
+        let mut listeners = vec![];
+
+        if let Ok(path) = std::env::var("SERVER_SOCKET_PATH") {
+            let mode = std::env::var("SERVER_SOCKET_MODE")
+                .unwrap_or_else(|_| DEFAULT_SOCKET_MODE.to_string());
+            listeners.push(Listener::unix(
+                PathBuf::from(path),
+                std::env::var("SERVER_SOCKET_OWNER").ok(),
+                std::env::var("SERVER_SOCKET_GROUP").ok(),
+                &mode,
+            )?);
+        }
+
+        match std::env::var("SERVER_ADDRESS") {
+            Ok(addr) => listeners.push(Listener::tcp(&addr)?),
+            Err(_) if listeners.is_empty() => {
+                log(
+                    "SERVER_ADDRESS environment variable is not set, using localhost:4242 default\n",
+                    LogInfo::Error,
+                )?;
+                listeners.push(Listener::tcp(DEFAULT_ADDRESS)?);
+            }
+            Err(_) => (),
+        }
+        Ok(listeners)
+    }
+
+    /// Non blocking, return the new connection and a description of its address
+    pub(crate) fn accept(&self) -> io::Result<(Stream, String)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                Ok((Stream::Tcp(stream), addr.to_string()))
+            }
+            Listener::Unix { listener, path } => {
+                let (stream, _) = listener.accept()?;
+                Ok((Stream::Unix(stream), path.display().to_string()))
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Listener::Unix { path, .. } = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn parse_octal_mode() -> Result<()> {
+        assert_eq!(parse_mode("0700")?, 0o700);
+        assert_eq!(parse_mode("0o660")?, 0o660);
+        assert!(parse_mode("0o999").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn unix_socket_permissions() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("taskmaster-{}.sock", std::process::id()));
+        let uid = unsafe { libc::getuid() };
+        {
+            let listener = Listener::unix(path.clone(), Some(uid.to_string()), None, "0660")?;
+            let metadata = fs::metadata(&path)?;
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o660);
+            assert_eq!(metadata.uid(), uid);
+            // the directory the socket was bound in is gone
+            let private = format!(".{}", path.file_name().unwrap().to_string_lossy());
+            assert!(!fs::read_dir(std::env::temp_dir())?
+                .flatten()
+                .any(|entry| entry.file_name().to_string_lossy().starts_with(&private)));
+
+            let _client = UnixStream::connect(&path)?;
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            assert!(listener.accept().is_ok());
+
+            // the socket is still in use
+            assert!(Listener::unix(path.clone(), None, None, "0700").is_err());
+        }
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_user() {
+        assert!(user_id("this-user-does-not-exist").is_err());
+        assert!(group_id("this-group-does-not-exist").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+mod auth;
 mod client;
+mod listener;
 
-pub use client::*;
+pub(crate) use auth::token_from_env;
@@ -1,7 +1,6 @@
 mod controller;
 mod model;
 
-pub use controller::*;
 use daemonize::Daemon;
 pub use daemonize::{Error, Result};
 use logger::LogInfo;
@@ -1,15 +1,32 @@
-use std::{
-    io::BufReader,
-    net::{SocketAddr, TcpStream},
-};
+use std::{io::BufReader, net::TcpStream, os::unix::net::UnixStream, time::Instant};
+
+use supervisor::Follow;
+
+/// Connection of a control client, either over tcp or a unix socket
+pub enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
 
 pub struct Client {
-    pub(crate) stream: TcpStream,
-    pub(crate) addr: SocketAddr,
-    pub(crate) reader: BufReader<TcpStream>,
+    pub(crate) stream: Stream,
+    pub(crate) addr: String,
+    pub(crate) reader: BufReader<Stream>,
+    // only `hello` and `auth` are accepted until this is set
+    pub(crate) authenticated: bool,
+    // `tail -f` being answered, until the next request of the client
+    pub(crate) following: Option<Following>,
+}
+
+pub struct Following {
+    // id of the tail request
+    pub(crate) id: u64,
+    pub(crate) follow: Follow,
+    pub(crate) last_sent: Instant,
 }
 
-#[derive(Default)]
 pub struct Clients {
     pub(crate) clients: Vec<Client>,
+    // shared secret clients must send, no authentication when unset
+    pub(crate) token: Option<String>,
 }
@@ -0,0 +1,10 @@
+use std::{net::TcpListener, os::unix::net::UnixListener, path::PathBuf};
+
+/// Endpoint the control clients connect to
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix {
+        listener: UnixListener,
+        path: PathBuf,
+    },
+}
@@ -1,3 +1,5 @@
 mod client;
+mod listener;
 
 pub use client::*;
+pub use listener::*;
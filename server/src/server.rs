@@ -1,25 +1,20 @@
 use core::time;
 use daemonize::Result;
 use libc::{SIGCHLD, SIGHUP};
-use signal_hook::consts::{FORBIDDEN, TERM_SIGNALS};
-use signal_hook::flag;
+use signal_hook::consts::FORBIDDEN;
 use signal_hook::iterator::exfiltrator::WithOrigin;
 use signal_hook::iterator::SignalsInfo;
-use std::net::TcpListener;
-use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
-use std::sync::{mpsc, Arc};
 use std::{io, thread};
 
 use supervisor::Programs;
 
-use crate::Clients;
+use crate::{controller::token_from_env, Clients, Listener};
 
 /// Send any signal received into a channel for the main loop to deal with.
 fn register_signal_hook(sender: Sender<i32>) -> Result<()> {
-    let mut forbidden = vec![SIGCHLD];
-    forbidden.extend(FORBIDDEN);
-    let sigs = (1..32).filter(|s| !forbidden.contains(s));
+    let sigs = (1..32).filter(|s| !FORBIDDEN.contains(s));
     let mut signals = SignalsInfo::<WithOrigin>::new(sigs)?;
     let handle = signals.handle();
 
@@ -34,24 +29,12 @@ fn register_signal_hook(sender: Sender<i32>) -> Result<()> {
 pub fn server() -> Result<()> {
     let mut programs = Programs::new(true)?;
 
-    let addr = match std::env::var("SERVER_ADDRESS") {
-        Ok(addr) => addr,
-        Err(_) => {
-            logger::log(
-                "SERVER_ADDRESS environment variable is not set, using localhost:4242 default",
-                logger::LogInfo::Error,
-            )?;
-            "127.0.0.1:4242".to_string()
-        }
-    };
-
-    let listener = TcpListener::bind(addr)?;
+    let listeners = Listener::from_env()?;
     let (tx, rx): (Sender<i32>, Receiver<i32>) = mpsc::channel();
 
     let _ = thread::spawn(|| register_signal_hook(tx));
 
-    listener.set_nonblocking(true)?;
-    let mut clients = Clients::default();
+    let mut clients = Clients::new(token_from_env()?);
 
     loop {
         // eprintln!("sleeping");
@@ -66,6 +49,9 @@ pub fn server() -> Result<()> {
             let v = rx.recv_timeout(time::Duration::from_millis(100));
             match v {
                 Ok(SIGHUP) => programs = programs.update_config()?, // sigup et down to handle here
+                // a child changed state, deal with it right away instead of
+                // waiting for the next check
+                Ok(SIGCHLD) => programs.reap_children()?,
                 Ok(sig) => {
                     logger::log(
                         format!("Received signal {} on server\n", sig),
@@ -85,31 +71,28 @@ pub fn server() -> Result<()> {
             }
         }
 
-        match listener.accept() {
-            Ok((stream, addr)) => {
-                clients.add_client(stream, addr)?;
-            }
-            Err(e) => {
-                if e.kind() != io::ErrorKind::WouldBlock {
-                    eprintln!("Error : {:?}", e)
+        for listener in &listeners {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    clients.add_client(stream, addr)?;
+                }
+                Err(e) => {
+                    if e.kind() != io::ErrorKind::WouldBlock {
+                        eprintln!("Error : {:?}", e)
+                    }
                 }
             }
         }
 
         if !clients.read_clients(&mut programs)? {
-            programs
-                .programs
-                .iter_mut()
-                .for_each(|(_, p)| p.kill_processes());
+            programs.shutdown();
             logger::log("Exiting server\n".to_string(), logger::LogInfo::Info)?;
             break;
         };
 
-        // check status of children
-        // check_child_status
+        // exits are handled on SIGCHLD, this only drives the timeouts
+        // (startsecs, stoptime) of the children
         programs.check()?;
-
-        thread::sleep(time::Duration::from_millis(300));
     }
     Ok(())
 }
@@ -0,0 +1,68 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::model::Backoff;
+
+// random number between 0 and 1, RandomState is seeded differently
+// each time, which is enough to spread the retries
+fn random_unit() -> f64 {
+    let value = RandomState::new().build_hasher().finish();
+    value as f64 / u64::MAX as f64
+}
+
+impl Backoff {
+    // delay to wait before the given restart attempt (starting at 1)
+    pub fn delay(&self, attempt: u8) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let secs = (self.base as f64 * self.multiplier.powi(exponent)).min(self.max as f64);
+        let jitter = secs * self.jitter.clamp(0.0, 1.0) * (2.0 * random_unit() - 1.0);
+        Duration::from_secs_f64((secs + jitter).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_exponential() {
+        let backoff = Backoff {
+            base: 1,
+            multiplier: 2.0,
+            max: 60,
+            jitter: 0.0,
+        };
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(2), Duration::from_secs(2));
+        assert_eq!(backoff.delay(3), Duration::from_secs(4));
+        assert_eq!(backoff.delay(4), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_delay_capped() {
+        let backoff = Backoff {
+            base: 5,
+            multiplier: 3.0,
+            max: 30,
+            jitter: 0.0,
+        };
+        assert_eq!(backoff.delay(2), Duration::from_secs(15));
+        assert_eq!(backoff.delay(3), Duration::from_secs(30));
+        assert_eq!(backoff.delay(200), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let backoff = Backoff {
+            base: 10,
+            multiplier: 1.0,
+            max: 60,
+            jitter: 0.5,
+        };
+        for _ in 0..100 {
+            let delay = backoff.delay(1);
+            assert!(delay >= Duration::from_secs(5) && delay <= Duration::from_secs(15));
+        }
+    }
+}
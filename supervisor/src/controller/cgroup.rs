@@ -0,0 +1,237 @@
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::model::{Cgroup, Error, ProcessCgroup, Program, Result};
+
+// period of cpu.max, in microseconds
+const CPU_PERIOD: u64 = 100_000;
+
+fn write(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value).map_err(|e| Error::Cgroup(format!("{} : {e}", path.display())))
+}
+
+// every cgroup v2 directory has this file
+fn is_cgroup(path: &Path) -> bool {
+    path.join("cgroup.controllers").exists()
+}
+
+impl Cgroup {
+    // controller, interface file and value of each limit, "max" when unset
+    fn limits(&self) -> [(&'static str, &'static str, Option<String>); 3] {
+        [
+            (
+                "memory",
+                "memory.max",
+                self.memory_max.map(|bytes| bytes.to_string()),
+            ),
+            (
+                "cpu",
+                "cpu.max",
+                self.cpu_max.map(|cpus| {
+                    let quota = (cpus * CPU_PERIOD as f64).round() as u64;
+                    format!("{} {CPU_PERIOD}", quota.max(1000))
+                }),
+            ),
+            (
+                "pids",
+                "pids.max",
+                self.pids_max.map(|pids| pids.to_string()),
+            ),
+        ]
+    }
+
+    // enable the controllers of the limits down to the cgroup of the
+    // program, from the first ancestor that is a cgroup
+    fn enable_controllers(&self, parent: &Path) -> Result<()> {
+        let controllers = self
+            .limits()
+            .into_iter()
+            .filter(|(_, _, value)| value.is_some())
+            .map(|(controller, _, _)| format!("+{controller}"))
+            .collect::<Vec<_>>();
+        if controllers.is_empty() {
+            return Ok(());
+        }
+
+        let mut ancestors = parent
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| is_cgroup(dir))
+            .collect::<Vec<_>>();
+        ancestors.reverse();
+        ancestors.push(parent);
+        for dir in ancestors {
+            write(&dir.join("cgroup.subtree_control"), &controllers.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+// `parent` has to be in a cgroup v2 hierarchy the server can write
+fn check_parent(name: &str, parent: &str) -> Result<()> {
+    let Some(existing) = Path::new(parent).ancestors().find(|dir| dir.exists()) else {
+        return Err(Error::Cgroup(format!("{parent} doesn't exist")));
+    };
+    if !is_cgroup(existing) {
+        return Err(Error::Cgroup(format!(
+            "{parent} is not in a cgroup v2 hierarchy"
+        )));
+    }
+    let path = CString::new(existing.as_os_str().as_bytes())
+        .map_err(|_| Error::Cgroup(format!("Invalid path {parent}")))?;
+    if unsafe { libc::access(path.as_ptr(), libc::W_OK) } != 0 {
+        return Err(Error::Privileges(format!(
+            "Program {name} can't create its cgroup in {}",
+            existing.display()
+        )));
+    }
+    Ok(())
+}
+
+impl Program {
+    pub fn check_cgroup(&self) -> Result<()> {
+        match &self.cgroup {
+            Some(cgroup) => check_parent(&self.name, &cgroup.parent),
+            None => Ok(()),
+        }
+    }
+
+    fn cgroup_path(&self) -> Option<PathBuf> {
+        self.cgroup
+            .as_ref()
+            .map(|cgroup| Path::new(&cgroup.parent).join(&self.name))
+    }
+
+    // the cgroup of the program with its current limits, and the one of
+    // the process inside. Both are kept when the process is gone
+    pub fn create_cgroup(&self, process_number: u8) -> Result<Option<ProcessCgroup>> {
+        let (Some(cgroup), Some(path)) = (&self.cgroup, self.cgroup_path()) else {
+            return Ok(None);
+        };
+        let parent = Path::new(&cgroup.parent);
+        fs::create_dir_all(&path)
+            .map_err(|e| Error::Cgroup(format!("{} : {e}", path.display())))?;
+        cgroup.enable_controllers(parent)?;
+        for (_, file, value) in cgroup.limits() {
+            let file = path.join(file);
+            match value {
+                Some(value) => write(&file, &value)?,
+                // a limit removed from the config
+                None if file.exists() => write(&file, "max")?,
+                None => (),
+            }
+        }
+
+        let process = path.join(process_number.to_string());
+        fs::create_dir_all(&process)
+            .map_err(|e| Error::Cgroup(format!("{} : {e}", process.display())))?;
+        Ok(Some(ProcessCgroup {
+            path: process,
+            kill: cgroup.kill,
+        }))
+    }
+
+    // best effort once the processes are gone, a cgroup that still has
+    // some can't be removed
+    pub fn remove_cgroup(&self) {
+        let Some(path) = self.cgroup_path() else {
+            return;
+        };
+        if let Ok(entries) = fs::read_dir(&path) {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .for_each(|entry| _ = fs::remove_dir(entry.path()));
+        }
+        _ = fs::remove_dir(&path);
+    }
+}
+
+impl ProcessCgroup {
+    // move the child in the cgroup before exec, the processes it starts
+    // will be in it too
+    pub fn apply(&self, command: &mut Command) -> Result<()> {
+        let path = self.path.join("cgroup.procs");
+        let procs = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::Cgroup(format!("{} : {e}", path.display())))?;
+        // only async signal safe calls between fork and exec, writing 0
+        // moves the process writing it
+        unsafe {
+            command.pre_exec(move || {
+                if libc::write(procs.as_raw_fd(), b"0".as_ptr().cast(), 1) != 1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    pub fn pids(&self) -> Vec<libc::pid_t> {
+        fs::read_to_string(self.path.join("cgroup.procs"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|pid| pid.parse().ok())
+            .collect()
+    }
+
+    pub fn signal(&self, sig: libc::c_int) {
+        for pid in self.pids() {
+            unsafe { libc::kill(pid, sig) };
+        }
+    }
+
+    // cgroup.kill is only there since linux 5.14
+    pub fn kill(&self) {
+        if fs::write(self.path.join("cgroup.kill"), "1").is_err() {
+            self.signal(libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let cgroup: Cgroup =
+            serde_yaml::from_str("memory_max: 512MB\ncpu_max: 1.5\nkill: true").unwrap();
+        assert_eq!(cgroup.parent, "/sys/fs/cgroup/taskmaster");
+        assert!(cgroup.kill);
+        assert_eq!(
+            cgroup.limits(),
+            [
+                ("memory", "memory.max", Some((512 << 20).to_string())),
+                ("cpu", "cpu.max", Some("150000 100000".to_string())),
+                ("pids", "pids.max", None),
+            ]
+        );
+
+        let cgroup = |yaml: &str| serde_yaml::from_str::<Cgroup>(yaml);
+        assert!(cgroup("cpu_max: 0").is_err());
+        assert!(cgroup("memory_max: 0").is_err());
+        assert!(cgroup("pids: 10").is_err());
+    }
+
+    #[test]
+    fn test_check_parent() {
+        let temp_dir = std::env::temp_dir().join("taskmaster");
+        assert!(matches!(
+            check_parent("web", &temp_dir.display().to_string()),
+            Err(Error::Cgroup(_))
+        ));
+        assert!(matches!(
+            check_parent("web", "relative"),
+            Err(Error::Cgroup(_))
+        ));
+    }
+}
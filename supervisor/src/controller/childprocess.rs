@@ -1,17 +1,20 @@
-use crate::model::{AutoRestart, ChildExitStatus, ChildProcess, Program, ProgramState};
+use super::logfile::open_log;
+use crate::model::{
+    AutoRestart, ChildExitStatus, ChildProcess, GroupSignals, Health, LogFile, Notify,
+    NotifySocket, ProcessStatus, Program, ProgramState, Signal,
+};
 
 use crate::model::{Error, Result};
 use logger::{log, LogInfo};
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use libc::kill;
 use libc::umask;
-use std::fs::File;
-use std::os::unix::process::ExitStatusExt;
-use std::process::{Command, Stdio};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, Stdio};
 
 // FnOnce limits the amount of time a closure can be called
 // as we run it in a loop, it's always a new one
@@ -25,40 +28,92 @@ pub fn with_umask<F: FnOnce() -> Result<ChildProcess>>(mask: u16, f: F) -> Resul
     }
 }
 
+// a rotated file is written by the supervisor through a pipe, any
+// other file is given to the process
+fn output(path: &str, program: &Program) -> Result<(Stdio, Option<LogFile>)> {
+    match &program.rotation {
+        Some(rotation) if !path.starts_with("/dev/") => Ok((
+            Stdio::piped(),
+            Some(LogFile::open(path, program.append, rotation.clone())?),
+        )),
+        _ => Ok((
+            Stdio::from(open_log(Path::new(path), program.append)?),
+            None,
+        )),
+    }
+}
+
+// not reaped yet, once it is its pid and its process group can be given
+// to other processes
+fn is_alive(child: &mut Child) -> bool {
+    matches!(child.try_wait(), Ok(None))
+}
+
 impl ChildProcess {
     pub fn start(program: &Program, process_number: u8) -> Result<ChildProcess> {
+        let program = &program.expanded(process_number)?;
         let umask = u16::from_str_radix(&program.umask, 8).unwrap_or(0o022);
 
         with_umask(umask, || {
-            let mut command = Command::new(&program.cmd.0);
+            let mut command = if program.shell {
+                let mut command = Command::new("/bin/sh");
+                command.arg("-c").arg(&program.cmd.line);
+                command
+            } else {
+                let mut command = Command::new(&program.cmd.program);
+                command.args(&program.cmd.args);
+                command
+            };
 
-            if !program.cmd.1.is_empty() {
-                command.args(&program.cmd.1);
+            command.current_dir(&program.working_dir);
+            // its own process group, the processes it starts can be
+            // signaled with it
+            command.process_group(0);
+
+            let cgroup = program.create_cgroup(process_number)?;
+            if let Some(cgroup) = &cgroup {
+                cgroup.apply(&mut command)?;
             }
+            program.limits.apply(&mut command);
 
-            command.current_dir(&program.working_dir);
+            // USER, LOGNAME and HOME can still be set in env
+            let credentials = program.credentials()?;
+            if let Some(credentials) = &credentials {
+                credentials.apply(&mut command);
+            }
 
             if let Some(env_vars) = &program.env {
                 command.envs(env_vars);
             }
 
-            let out_path = &program.stdout;
-            let path = Path::new(&out_path);
-            if let Some(dir) = path.parent() {
-                std::fs::create_dir_all(dir)?;
+            let (stdout, stdout_log) = output(&program.stdout, program)?;
+            command.stdout(stdout);
+            let (stderr, stderr_log) = output(&program.stderr, program)?;
+            command.stderr(stderr);
+
+            // the process sends its readiness, status and heartbeats on this socket
+            let watchdog = program.watchdog.as_ref().filter(|w| w.file.is_none());
+            let notify = if program.notify || watchdog.is_some() {
+                let notify = NotifySocket::bind(credentials.as_ref())?;
+                command.env("NOTIFY_SOCKET", &notify.path);
+                if let Some(watchdog) = watchdog {
+                    command.env(
+                        "WATCHDOG_USEC",
+                        (u64::from(watchdog.timeout) * 1_000_000).to_string(),
+                    );
+                }
+                Some(Arc::new(notify))
+            } else {
+                None
+            };
+
+            let mut child = command.spawn()?;
+            if let (Some(log_file), Some(stdout)) = (stdout_log, child.stdout.take()) {
+                log_file.forward(stdout);
             }
-            let file = File::create(path)?;
-            command.stdout(Stdio::from(file));
-
-            let err_path = &program.stderr;
-            let path = Path::new(&err_path);
-            if let Some(dir) = path.parent() {
-                std::fs::create_dir_all(dir)?;
+            if let (Some(log_file), Some(stderr)) = (stderr_log, child.stderr.take()) {
+                log_file.forward(stderr);
             }
-            let file = File::create(path)?;
-            command.stderr(Stdio::from(file));
-
-            let child = command.spawn()?;
 
             _ = log(
                 format!(
@@ -75,6 +130,18 @@ impl ChildProcess {
                 start_secs: Some(Instant::now()),
                 end_time: None, // killed, fatal, stopped, exited -- state that cannot be changed
                 restart_count: 0,
+                next_retry: None,
+                health: Health::default(),
+                restart_requested: None,
+                notify: Notify {
+                    socket: notify,
+                    ..Notify::default()
+                },
+                cgroup,
+                group_signals: GroupSignals {
+                    stop: program.stop_as_group,
+                    kill: program.kill_as_group || program.stop_as_group,
+                },
             })
         })
     }
@@ -111,6 +178,30 @@ impl ChildProcess {
         }
     }
 
+    pub fn pid(&self) -> Option<u32> {
+        self.child
+            .as_ref()
+            .and_then(|child| child.lock().ok().map(|c| c.id()))
+    }
+
+    // peek at the child with waitid and WNOWAIT, so the zombie is left for
+    // `get_child_exit_status` to reap through `try_wait`
+    pub fn has_exited(&self) -> bool {
+        let Some(pid) = self.pid() else {
+            return false;
+        };
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        };
+        ret == 0 && unsafe { info.si_pid() } == pid as libc::pid_t
+    }
+
     pub fn is_exit_status_in_config(&self, config: &Program) -> bool {
         match self.exit_status {
             ChildExitStatus::Exited(status) => config.exitcodes.contains(&(status as u8)),
@@ -122,6 +213,28 @@ impl ChildProcess {
         let _ = self.send_kill(9);
     }
 
+    // to the process, its process group or every process of its cgroup,
+    // as set in the config. True when it was sent
+    fn deliver(&self, pid: libc::pid_t, sig: libc::c_int) -> bool {
+        let as_group = match sig {
+            libc::SIGKILL => self.group_signals.kill,
+            _ => self.group_signals.stop,
+        };
+        match self.cgroup.as_ref().filter(|cgroup| cgroup.kill) {
+            Some(cgroup) if sig == libc::SIGKILL => {
+                cgroup.kill();
+                true
+            }
+            Some(cgroup) => {
+                cgroup.signal(sig);
+                true
+            }
+            // the process group has the pid of the process
+            None if as_group => unsafe { libc::killpg(pid, sig) == 0 },
+            None => unsafe { kill(pid, sig) == 0 },
+        }
+    }
+
     pub fn send_kill(&mut self, sig: u8) -> Result<()> {
         if let Some(child) = self.child.as_ref() {
             self.end_time = Some(Instant::now());
@@ -129,7 +242,9 @@ impl ChildProcess {
                 message: e.to_string(),
             })?;
 
-            let _ = unsafe { kill(child.id() as libc::pid_t, sig as libc::c_int) };
+            if is_alive(&mut child) {
+                self.deliver(child.id() as libc::pid_t, sig as libc::c_int);
+            }
             if sig == 9 {
                 let _ = child.wait(); // reap zombies
             }
@@ -137,10 +252,51 @@ impl ChildProcess {
         Ok(())
     }
 
+    // send a signal to the process while it runs, true when it was sent
+    pub fn signal(&self, signal: Signal) -> bool {
+        if self.exit_status != ChildExitStatus::Running {
+            return false;
+        }
+        let Some(Ok(mut child)) = self.child.as_ref().map(|child| child.lock()) else {
+            return false;
+        };
+        is_alive(&mut child)
+            && self.deliver(
+                child.id() as libc::pid_t,
+                libc::c_int::from(signal.number()),
+            )
+    }
+
     pub fn stop(&mut self, sig: u8) -> Result<()> {
         self.state = ProgramState::Stopping;
         self.send_kill(sig)
     }
+
+    // called after `stop`, true once the process is gone. Sends SIGKILL when
+    // it is still running after stoptime
+    pub fn finish_stop(&mut self, stop_time: u16) -> bool {
+        match self.get_child_exit_status() {
+            Ok(ChildExitStatus::Running) => {
+                let elapsed = self.end_time.map_or(Duration::ZERO, |t| t.elapsed());
+                if elapsed < Duration::from_secs(stop_time.into()) {
+                    return false;
+                }
+                self.kill_program();
+                self.state = ProgramState::Killed;
+            }
+            Ok(exit_status) => {
+                self.exit_status = exit_status;
+                self.state = ProgramState::Stopped;
+            }
+            Err(_) => self.state = ProgramState::Error,
+        }
+        // what the process started and left behind
+        if let Some(cgroup) = self.cgroup.as_ref().filter(|cgroup| cgroup.kill) {
+            cgroup.kill();
+        }
+        true
+    }
+
     pub fn restart(&mut self, sig: u8) -> Result<()> {
         self.state = ProgramState::Restarting;
         self.send_kill(sig)
@@ -151,18 +307,72 @@ impl ChildProcess {
         let restart_count = self.restart_count;
         let updated_child = ChildProcess::start(program, process_number)?;
         self.child = updated_child.child;
+        self.start_secs = updated_child.start_secs;
         self.restart_count = restart_count;
+        self.health = Health::default();
+        self.restart_requested = None;
+        self.notify = updated_child.notify;
+        self.cgroup = updated_child.cgroup;
+        self.group_signals = updated_child.group_signals;
         Ok(())
     }
 
+    // gracefully stop a running process that doesn't do its job, `check`
+    // restarts it after the backoff delay once it has exited
+    pub fn request_restart(&mut self, config: &Program) -> Result<()> {
+        self.restart_requested = Some(Instant::now());
+        self.send_kill(config.stop_signal.number())
+    }
+
     pub fn increment_start_retries(&mut self) {
         self.restart_count += 1;
     }
 
+    // wait for the backoff delay of the program before the next restart
+    // attempt, which is done by `check` once the delay is over
+    pub fn schedule_retry(&mut self, config: &Program) {
+        self.state = ProgramState::Backoff;
+        self.increment_start_retries();
+        self.next_retry = Some(Instant::now() + config.backoff.delay(self.restart_count));
+    }
+
+    pub fn next_retry_in(&self) -> Option<Duration> {
+        self.next_retry
+            .map(|next_retry| next_retry.saturating_duration_since(Instant::now()))
+    }
+
+    // the process needs to stay up for `startsecs` before the start
+    // is considered successful
+    pub fn has_survived_start_secs(&self, config: &Program) -> bool {
+        self.start_secs.is_none_or(|start_time| {
+            start_time.elapsed() >= Duration::from_secs(config.start_secs.into())
+        })
+    }
+
+    // startsecs are over, and the process said it is ready if it has to
+    pub fn is_started(&self, config: &Program) -> bool {
+        self.has_survived_start_secs(config) && (!config.notify || self.notify.ready)
+    }
+
+    pub fn status(&self, process_number: usize) -> ProcessStatus {
+        let alive = self.exit_status == ChildExitStatus::Running;
+        ProcessStatus {
+            index: process_number,
+            state: self.state.clone(),
+            pid: self.pid().filter(|_| alive),
+            uptime: self
+                .start_secs
+                .filter(|_| alive)
+                .map(|start_time| start_time.elapsed().as_secs()),
+            exit_status: self.exit_status.clone(),
+            restarts: self.restart_count,
+            next_retry: self.next_retry_in().map(|delay| delay.as_secs_f64()),
+            status_text: self.notify.status.clone().filter(|_| alive),
+        }
+    }
+
     pub fn check(&mut self, config: &Program, process_number: u8) -> Result<()> {
-        let elapsed_start_time = self.start_secs.map_or(0, |start_time| {
-            Instant::now().duration_since(start_time).as_secs()
-        });
+        self.receive_notifications();
         let elapsed_exit_time = self.end_time.map_or(0, |exit_time| {
             Instant::now().duration_since(exit_time).as_secs()
         });
@@ -171,6 +381,29 @@ impl ChildProcess {
             ProgramState::Starting => {
                 self.exit_status = self.get_child_exit_status()?;
                 match &self.exit_status {
+                    // exited before startsecs, the start failed
+                    ChildExitStatus::Exited(_) if !self.has_survived_start_secs(config) => {
+                        if self.restart_count >= config.start_retries {
+                            let _ = log(
+                                format!(
+                                    "{}--{}: From starting to fatal\n",
+                                    config.name, process_number
+                                ),
+                                LogInfo::Info,
+                            );
+                            self.state = ProgramState::Fatal;
+                        } else {
+                            let _ = log(
+                                format!(
+                                    "{}--{}: From starting to backoff\n",
+                                    config.name, process_number
+                                ),
+                                LogInfo::Info,
+                            );
+                            self.schedule_retry(config);
+                        }
+                        Ok(())
+                    }
                     ChildExitStatus::Exited(_) => {
                         if self.is_exit_status_in_config(config) {
                             let _ = log(
@@ -211,15 +444,7 @@ impl ChildProcess {
                                         ),
                                         LogInfo::Info,
                                     );
-                                    self.state = ProgramState::Backoff;
-                                    self.increment_start_retries();
-                                    if let Err(e) = self.rerun_program(config, process_number) {
-                                        let _ = log(
-                                            format!("Failed to rerun program: {}\n", e),
-                                            LogInfo::Error,
-                                        );
-                                        return Err(e);
-                                    }
+                                    self.schedule_retry(config);
                                 }
                             }
                         }
@@ -227,15 +452,17 @@ impl ChildProcess {
                         Ok(())
                     }
                     ChildExitStatus::Running => {
-                        self.restart_count = 0;
-                        let _ = log(
-                            format!(
-                                "{}--{}: From starting to running\n",
-                                config.name, process_number
-                            ),
-                            LogInfo::Info,
-                        );
-                        self.state = ProgramState::Running;
+                        if self.is_started(config) {
+                            self.restart_count = 0;
+                            let _ = log(
+                                format!(
+                                    "{}--{}: From starting to running\n",
+                                    config.name, process_number
+                                ),
+                                LogInfo::Info,
+                            );
+                            self.state = ProgramState::Running;
+                        }
                         Ok(())
                     }
                     ChildExitStatus::NonExistent => unreachable!(),
@@ -263,9 +490,21 @@ impl ChildProcess {
                     ChildExitStatus::WaitError(e) => Err(Error::WaitError(e.clone())),
                 }
             }
-            ProgramState::Running => {
+            ProgramState::Running | ProgramState::Unhealthy => {
                 self.exit_status = self.get_child_exit_status()?;
                 match &self.exit_status {
+                    // stopped by the health check or the watchdog, restarted after the backoff delay
+                    ChildExitStatus::Exited(_) if self.restart_requested.is_some() => {
+                        let _ = log(
+                            format!(
+                                "{}--{}: From {} to backoff\n",
+                                config.name, process_number, self.state
+                            ),
+                            LogInfo::Info,
+                        );
+                        self.schedule_retry(config);
+                        Ok(())
+                    }
                     ChildExitStatus::Exited(_) => {
                         if self.is_exit_status_in_config(config) {
                             let _ = log(
@@ -307,30 +546,77 @@ impl ChildProcess {
                                         ),
                                         LogInfo::Info,
                                     );
-                                    self.state = ProgramState::Backoff;
-                                    self.increment_start_retries();
-                                    if let Err(e) = self.rerun_program(config, process_number) {
-                                        let _ = log(
-                                            format!("Failed to rerun program: {}\n", e),
-                                            LogInfo::Error,
-                                        );
-                                        return Err(e);
-                                    }
+                                    self.schedule_retry(config);
                                 }
                             }
                         }
                         Ok(())
                     }
-                    ChildExitStatus::Running => Ok(()),
+                    // being stopped to be restarted, killed if still alive after stoptime
+                    ChildExitStatus::Running if self.restart_requested.is_some() => {
+                        if elapsed_exit_time >= (config.stop_time as u64) {
+                            self.kill_program();
+                        }
+                        Ok(())
+                    }
+                    ChildExitStatus::Running => {
+                        self.check_watchdog(config, process_number)?;
+                        if self.restart_requested.is_none() {
+                            self.check_health(config, process_number)?;
+                        }
+                        Ok(())
+                    }
                     ChildExitStatus::NonExistent => unreachable!(),
                     ChildExitStatus::WaitError(e) => Err(Error::WaitError(e.clone())),
                 }
             }
             ProgramState::Backoff => {
+                if let Some(next_retry) = self.next_retry {
+                    if Instant::now() < next_retry {
+                        return Ok(());
+                    }
+                    self.next_retry = None;
+                    if let Err(e) = self.rerun_program(config, process_number) {
+                        let _ = log(format!("Failed to rerun program: {}\n", e), LogInfo::Error);
+                        if self.restart_count >= config.start_retries {
+                            let _ = log(
+                                format!(
+                                    "{}--{}: From backoff to fatal\n",
+                                    config.name, process_number
+                                ),
+                                LogInfo::Info,
+                            );
+                            self.state = ProgramState::Fatal;
+                        } else {
+                            self.schedule_retry(config);
+                        }
+                        return Err(e);
+                    }
+                    let _ = log(
+                        format!(
+                            "{}--{}: Restart attempt {}\n",
+                            config.name, process_number, self.restart_count
+                        ),
+                        LogInfo::Info,
+                    );
+                    return Ok(());
+                }
+
                 self.exit_status = self.get_child_exit_status()?;
                 match &self.exit_status {
                     ChildExitStatus::Exited(_) => {
-                        if !self.is_exit_status_in_config(config) {
+                        if self.is_exit_status_in_config(config)
+                            && self.has_survived_start_secs(config)
+                        {
+                            let _ = log(
+                                format!(
+                                    "{}--{}: From backoff to exited\n",
+                                    config.name, process_number
+                                ),
+                                LogInfo::Info,
+                            );
+                            self.state = ProgramState::Exited;
+                        } else {
                             self.kill_program();
                             if self.restart_count >= config.start_retries {
                                 let _ = log(
@@ -354,14 +640,6 @@ impl ChildProcess {
                                         self.state = ProgramState::Pending;
                                     }
                                     _ => {
-                                        self.increment_start_retries();
-                                        if let Err(e) = self.rerun_program(config, process_number) {
-                                            let _ = log(
-                                                format!("Failed to rerun program: {}\n", e),
-                                                LogInfo::Error,
-                                            );
-                                            return Err(e);
-                                        }
                                         let _ = log(
                                             format!(
                                                 "{}--{}: Stay in backoff\n",
@@ -369,7 +647,7 @@ impl ChildProcess {
                                             ),
                                             LogInfo::Info,
                                         );
-                                        self.state = ProgramState::Backoff;
+                                        self.schedule_retry(config);
                                     }
                                 }
                             }
@@ -377,22 +655,21 @@ impl ChildProcess {
                         Ok(())
                     }
                     ChildExitStatus::Running => {
-                        let _ = log(
-                            format!(
-                                "{}--{}: From backoff to running\n",
-                                config.name, process_number
-                            ),
-                            LogInfo::Info,
-                        );
-                        self.state = ProgramState::Running;
-                        self.restart_count = 0;
+                        if self.is_started(config) {
+                            let _ = log(
+                                format!(
+                                    "{}--{}: From backoff to running\n",
+                                    config.name, process_number
+                                ),
+                                LogInfo::Info,
+                            );
+                            self.state = ProgramState::Running;
+                            self.restart_count = 0;
+                        }
                         Ok(())
                     }
                     ChildExitStatus::NonExistent => {
                         // starting previously failed
-                        if elapsed_start_time >= (config.start_secs as u64) {
-                            return Ok(());
-                        }
                         if self.restart_count >= config.start_retries {
                             let _ = log(
                                 format!(
@@ -403,19 +680,11 @@ impl ChildProcess {
                             );
                             self.state = ProgramState::Fatal;
                         } else {
-                            self.increment_start_retries();
-                            if let Err(e) = self.rerun_program(config, process_number) {
-                                let _ = log(
-                                    format!("Failed to rerun program: {}\n", e),
-                                    LogInfo::Error,
-                                );
-                                return Err(e);
-                            }
                             let _ = log(
                                 format!("{}--{}: Stay in backoff\n", config.name, process_number),
                                 LogInfo::Info,
                             );
-                            self.state = ProgramState::Backoff;
+                            self.schedule_retry(config);
                         }
                         Ok(())
                     }
@@ -484,16 +753,27 @@ impl ChildProcess {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::test_program;
     use std::fs;
     use std::os::unix::fs::OpenOptionsExt;
     use std::os::unix::fs::PermissionsExt;
 
     use crate::with_umask;
     use crate::AutoRestart;
+    use crate::Backoff;
+    use crate::Cgroup;
     use crate::ChildProcess;
-    use crate::StopSignal;
+    use crate::GroupSignals;
+    use crate::Health;
+    use crate::HealthCheck;
+    use crate::Limit;
+    use crate::Limits;
+    use crate::Notify;
+    use crate::Probe;
+    use crate::Signal;
 
     use crate::ProgramState;
+    use crate::Rotation;
     use std::fs::OpenOptions;
     use std::io::Write;
     use std::time::Instant;
@@ -534,6 +814,12 @@ mod tests {
                     start_secs: Some(Instant::now()),
                     end_time: None,
                     restart_count: 0,
+                    next_retry: None,
+                    health: Health::default(),
+                    restart_requested: None,
+                    notify: Notify::default(),
+                    cgroup: None,
+                    group_signals: GroupSignals::default(),
                 })
             });
         }
@@ -562,6 +848,12 @@ mod tests {
                     start_secs: Some(Instant::now()),
                     end_time: None,
                     restart_count: 0,
+                    next_retry: None,
+                    health: Health::default(),
+                    restart_requested: None,
+                    notify: Notify::default(),
+                    cgroup: None,
+                    group_signals: GroupSignals::default(),
                 })
             });
         }
@@ -570,26 +862,11 @@ mod tests {
     #[test]
     fn test_check_starting_running() -> Result<()> {
         let program = Program {
-            name: "sleep_working".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["5".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
             auto_restart: AutoRestart::Always,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_working", "/bin/sleep 5")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -601,28 +878,410 @@ mod tests {
     }
 
     #[test]
-    fn test_check_starting_exited() -> Result<()> {
+    fn test_start_shell() -> Result<()> {
+        let output = std::env::temp_dir().join(format!("taskmaster-shell-{}", std::process::id()));
+        let _defer = Defer(Some(|| {
+            let _ = fs::remove_file(&output);
+        }));
         let program = Program {
-            name: "sleep_exiting".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["0".to_string()]),
-            num_procs: 1,
+            shell: true,
+            ..test_program(
+                "shell",
+                &format!("echo hello | tr a-z A-Z > '{}'", output.display()),
+            )
+        };
 
-            auto_start: false,
+        let child_process = ChildProcess::start(&program, 0)?;
+        if let Some(child) = child_process.child.as_ref() {
+            child.lock().unwrap().wait()?;
+        }
+        assert_eq!(fs::read_to_string(&output)?, "HELLO\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_cgroup_kill() -> Result<()> {
+        // a cgroup v2 hierarchy root can write in, hybrid hosts mount it apart
+        let Some(root) = ["/sys/fs/cgroup", "/sys/fs/cgroup/unified"]
+            .into_iter()
+            .find(|root| Path::new(root).join("cgroup.controllers").exists())
+            .filter(|_| unsafe { libc::geteuid() } == 0)
+        else {
+            return Ok(());
+        };
+        let parent = Path::new(root).join(format!("taskmaster-test-{}", std::process::id()));
+        let program = Program {
+            shell: true,
+            working_dir: "/".to_string(),
+            cgroup: Some(Cgroup {
+                parent: parent.display().to_string(),
+                memory_max: None,
+                cpu_max: None,
+                pids_max: None,
+                kill: true,
+            }),
+            ..test_program("cgroup", "(trap '' TERM; exec sleep 60) & exec sleep 60")
+        };
+        let _defer = Defer(Some(|| {
+            program.remove_cgroup();
+            let _ = fs::remove_dir(&parent);
+        }));
+
+        let mut child_process = ChildProcess::start(&program, 0)?;
+        let cgroup = child_process.cgroup.clone().unwrap();
+        assert_eq!(cgroup.path, parent.join("cgroup").join("0"));
+        let wait_for = |count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while cgroup.pids().len() != count && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            cgroup.pids().len()
+        };
+        // the process and the one it started, which ignores the stop signal
+        assert_eq!(wait_for(2), 2);
+
+        child_process.stop(Signal::TERM.number())?;
+        while !child_process.finish_stop(program.stop_time) {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(child_process.state, ProgramState::Stopped);
+        assert_eq!(wait_for(0), 0);
+        Ok(())
+    }
+
+    // live processes of a process group
+    fn group_members(pgid: u32) -> usize {
+        fs::read_dir("/proc")
+            .unwrap()
+            .flatten()
+            .filter_map(|entry| fs::read_to_string(entry.path().join("stat")).ok())
+            .filter(|stat| {
+                // `pid (comm) state ppid pgrp ...`, comm can have spaces
+                let fields = stat[stat.rfind(')').unwrap() + 2..]
+                    .split(' ')
+                    .collect::<Vec<_>>();
+                fields[0] != "Z" && fields[2] == pgid.to_string()
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_stop_as_group() -> Result<()> {
+        let mut program = Program {
+            shell: true,
+            stop_as_group: true,
+            working_dir: "/".to_string(),
+            ..test_program("group", "sleep 60 & exec sleep 60")
+        };
+        let run = |program: &Program| -> Result<(ChildProcess, u32)> {
+            let child_process = ChildProcess::start(program, 0)?;
+            let pid = child_process.pid().unwrap();
+            // in its own process group
+            assert_eq!(
+                unsafe { libc::getpgid(pid as libc::pid_t) },
+                pid as libc::pid_t
+            );
+            Ok((child_process, pid))
+        };
+        let wait_for = |pgid: u32, count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while group_members(pgid) != count && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            group_members(pgid)
+        };
+
+        let (mut child_process, pid) = run(&program)?;
+        assert_eq!(wait_for(pid, 2), 2);
+        child_process.stop(Signal::TERM.number())?;
+        while !child_process.finish_stop(program.stop_time) {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(wait_for(pid, 0), 0);
+
+        // so does a signal sent with the signal command
+        let (mut child_process, pid) = run(&program)?;
+        assert_eq!(wait_for(pid, 2), 2);
+        assert!(child_process.signal(Signal::TERM));
+        assert_eq!(wait_for(pid, 0), 0);
+        child_process.get_child_exit_status()?;
+
+        // only the process gets the signal, the other one is left behind
+        program.stop_as_group = false;
+        let (mut child_process, pid) = run(&program)?;
+        let _defer = Defer(Some(|| unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }));
+        assert_eq!(wait_for(pid, 2), 2);
+        child_process.stop(Signal::TERM.number())?;
+        while !child_process.finish_stop(program.stop_time) {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(wait_for(pid, 1), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_signal_once_reaped() -> Result<()> {
+        let program = Program {
+            shell: true,
+            stop_as_group: true,
+            kill_as_group: true,
+            working_dir: "/".to_string(),
+            ..test_program("reaped", "sleep 60 & exit 0")
+        };
+        let mut child_process = ChildProcess::start(&program, 0)?;
+        let pgid = child_process.pid().unwrap();
+        let _defer = Defer(Some(|| unsafe {
+            libc::killpg(pgid as libc::pid_t, libc::SIGKILL);
+        }));
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while child_process.get_child_exit_status()? == ChildExitStatus::Running
+            && Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(group_members(pgid), 1);
+
+        // the process is reaped, its group is left alone like it would be
+        // once its pgid is given to another process
+        child_process.kill_program();
+        assert!(!child_process.signal(Signal::TERM));
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(group_members(pgid), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_as_user() -> Result<()> {
+        // switching user needs root
+        if unsafe { libc::geteuid() } != 0 {
+            return Ok(());
+        }
+        let output = std::env::temp_dir().join(format!("taskmaster-user-{}", std::process::id()));
+        let _defer = Defer(Some(|| {
+            let _ = fs::remove_file(&output);
+        }));
+        let program = Program {
+            shell: true,
+            working_dir: "/".to_string(),
+            user: Some("nobody".to_string()),
+            supplementary_groups: vec!["4242".to_string()],
+            stdout: output.display().to_string(),
+            ..test_program("user", "id -u; id -g; id -G; echo $USER $HOME")
+        };
+
+        let child_process = ChildProcess::start(&program, 0)?;
+        if let Some(child) = child_process.child.as_ref() {
+            child.lock().unwrap().wait()?;
+        }
+        let credentials = program.credentials()?.unwrap();
+        assert_eq!(
+            fs::read_to_string(&output)?,
+            format!(
+                "{uid}\n{gid}\n{gid} 4242\nnobody {home}\n",
+                uid = credentials.uid,
+                gid = credentials.gid,
+                home = credentials.user.unwrap().1
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_append() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("taskmaster-append-{}", std::process::id()));
+        let _defer = Defer(Some(|| {
+            let _ = fs::remove_dir_all(&dir);
+        }));
+        let mut program = Program {
+            num_procs: 2,
+            stdout: format!("{}/%(program_name)s-%(process_num)d.out", dir.display()),
+            append: true,
+            ..test_program("append", "/bin/echo hello")
+        };
+        let run = |program: &Program, process_number: u8| -> Result<()> {
+            let child_process = ChildProcess::start(program, process_number)?;
+            if let Some(child) = child_process.child.as_ref() {
+                child.lock().unwrap().wait()?;
+            }
+            Ok(())
+        };
+
+        run(&program, 0)?;
+        run(&program, 0)?;
+        run(&program, 1)?;
+        assert_eq!(
+            fs::read_to_string(dir.join("append-0.out"))?,
+            "hello\nhello\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("append-1.out"))?, "hello\n");
+
+        // truncated at each start
+        program.append = false;
+        run(&program, 0)?;
+        assert_eq!(fs::read_to_string(dir.join("append-0.out"))?, "hello\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_limits() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("taskmaster-limits-{}", std::process::id()));
+        let _defer = Defer(Some(|| {
+            let _ = fs::remove_dir_all(&dir);
+        }));
+        let program = Program {
+            shell: true,
+            limits: Limits {
+                nofile: Some(Limit {
+                    soft: 64,
+                    hard: 128,
+                }),
+                core: Some(Limit { soft: 0, hard: 0 }),
+                ..Limits::default()
+            },
+            stdout: format!("{}/limits.out", dir.display()),
+            ..test_program("limits", "ulimit -n; ulimit -c")
+        };
+        let run = |program: &Program, process_number: u8| -> Result<()> {
+            let child_process = ChildProcess::start(program, process_number)?;
+            if let Some(child) = child_process.child.as_ref() {
+                child.lock().unwrap().wait()?;
+            }
+            Ok(())
+        };
+
+        run(&program, 0)?;
+        assert_eq!(fs::read_to_string(dir.join("limits.out"))?, "64\n0\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_rotation() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("taskmaster-rotation-{}", std::process::id()));
+        let _defer = Defer(Some(|| {
+            let _ = fs::remove_dir_all(&dir);
+        }));
+        let stdout = dir.join("rotation.out");
+        let program = Program {
+            shell: true,
+            stdout: stdout.display().to_string(),
+            rotation: Some(Rotation {
+                maxbytes: 5,
+                backups: 3,
+                compress: false,
+            }),
+            ..test_program(
+                "rotation",
+                "echo one; sleep 0.2; echo two; sleep 0.2; echo three",
+            )
+        };
+
+        let child_process = ChildProcess::start(&program, 0)?;
+        if let Some(child) = child_process.child.as_ref() {
+            child.lock().unwrap().wait()?;
+        }
+        // the output is written by another thread
+        for _ in 0..40 {
+            if fs::read_to_string(&stdout)? == "three\n" {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        assert_eq!(fs::read_to_string(&stdout)?, "three\n");
+        assert_eq!(fs::read_to_string(dir.join("rotation.out.1"))?, "two\n");
+        assert_eq!(fs::read_to_string(dir.join("rotation.out.2"))?, "one\n");
+        Ok(())
+    }
+
+    fn unhealthy_program(restart: bool) -> Program {
+        Program {
             auto_restart: AutoRestart::Always,
+            start_retries: 3,
+            // retry right away
+            backoff: Backoff {
+                base: 0,
+                ..Backoff::default()
+            },
+            healthcheck: Some(HealthCheck {
+                probe: Probe::Cmd(("/bin/false".to_string(), vec![])),
+                interval: 0,
+                timeout: 1,
+                failure_threshold: 2,
+                restart,
+            }),
+            ..test_program("sleep_unhealthy", "/bin/sleep 5")
+        }
+    }
+
+    // run the checks until the state changes or the time runs out
+    fn check_until(
+        child_process: &mut ChildProcess,
+        program: &Program,
+        state: ProgramState,
+    ) -> Result<()> {
+        for _ in 0..40 {
+            child_process.check(program, 0)?;
+            if child_process.state == state {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_running_unhealthy() -> Result<()> {
+        let program = unhealthy_program(false);
+        let mut child_process = ChildProcess::start(&program, 0).unwrap();
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
+
+        check_until(&mut child_process, &program, ProgramState::Unhealthy)?;
+        assert_eq!(child_process.state, ProgramState::Unhealthy);
+        assert_eq!(child_process.health.failures, 2);
+
+        // healthy again once a probe succeeds
+        let mut healthy = program.clone();
+        healthy.healthcheck.as_mut().unwrap().probe = Probe::Cmd(("/bin/true".to_string(), vec![]));
+        check_until(&mut child_process, &healthy, ProgramState::Running)?;
+        assert_eq!(child_process.state, ProgramState::Running);
+        assert_eq!(child_process.health.failures, 0);
+
+        child_process.kill_program();
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_unhealthy_restart() -> Result<()> {
+        let program = unhealthy_program(true);
+        let mut child_process = ChildProcess::start(&program, 0).unwrap();
+        let pid = child_process.pid();
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
 
-            exitcodes: vec![0],
+        check_until(&mut child_process, &program, ProgramState::Backoff)?;
+        assert_eq!(child_process.state, ProgramState::Backoff);
+        assert_eq!(child_process.restart_count, 1);
+        // the backoff check restarts it
+        child_process.check(&program, 0)?;
+        assert_ne!(child_process.pid(), pid);
+        assert_eq!(child_process.health, Health::default());
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
 
+        child_process.kill_program();
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_starting_exited() -> Result<()> {
+        let program = Program {
+            auto_restart: AutoRestart::Always,
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_exiting", "/bin/sleep 0")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -634,28 +1293,52 @@ mod tests {
     }
 
     #[test]
-    fn test_check_starting_backoff() -> Result<()> {
+    fn test_check_starting_startsecs() -> Result<()> {
         let program = Program {
-            name: "sleep_backoff".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["2".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
             auto_restart: AutoRestart::Always,
+            start_retries: 3,
+            start_secs: 2,
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_startsecs", "/bin/sleep 5")
+        };
 
-            exitcodes: vec![0],
+        let mut child_process = ChildProcess::start(&program, 0).unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Starting);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
+        Ok(())
+    }
 
+    #[test]
+    fn test_check_starting_exited_before_startsecs() -> Result<()> {
+        let program = Program {
             start_retries: 3,
-            start_secs: 1,
+            start_secs: 5,
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_failed_start", "/bin/sleep 0")
+        };
 
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+        let mut child_process = ChildProcess::start(&program, 0).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        child_process.check(&program, 0)?;
+
+        assert_eq!(child_process.state, ProgramState::Backoff);
+        assert_eq!(child_process.restart_count, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_starting_backoff() -> Result<()> {
+        let program = Program {
+            auto_restart: AutoRestart::Always,
+            start_retries: 3,
+            start_secs: 1,
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_backoff", "/bin/sleep 2")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -675,26 +1358,10 @@ mod tests {
     #[test]
     fn test_check_starting_pending() -> Result<()> {
         let program = Program {
-            name: "sleep_pending".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["3".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
-            auto_restart: AutoRestart::Never,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_pending", "/bin/sleep 3")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -714,26 +1381,11 @@ mod tests {
     #[test]
     fn test_check_backoff_fatal() -> Result<()> {
         let program = Program {
-            name: "sleep_fatal".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["3".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
             auto_restart: AutoRestart::Always,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_fatal", "/bin/sleep 3")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -757,26 +1409,11 @@ mod tests {
     #[test]
     fn test_check_backoff_backoff() -> Result<()> {
         let program = Program {
-            name: "sleep_fatal".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["3".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
             auto_restart: AutoRestart::Always,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_fatal", "/bin/sleep 3")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -798,6 +1435,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_check_backoff_waits_delay() -> Result<()> {
+        let program = Program {
+            auto_restart: AutoRestart::Always,
+            start_retries: 3,
+            start_secs: 1,
+            stop_signal: Signal::USR1,
+            backoff: Backoff {
+                base: 1,
+                multiplier: 2.0,
+                max: 10,
+                jitter: 0.0,
+            },
+            ..test_program("sleep_backoff_delay", "/bin/sleep 3")
+        };
+
+        let mut child_process = ChildProcess::start(&program, 0).unwrap();
+        let pid = child_process.pid();
+        use libc::{kill, SIGKILL};
+        let _ = unsafe { kill(pid.unwrap() as libc::pid_t, SIGKILL) };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        // exited before startsecs, the retry is delayed
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Backoff);
+        assert!(child_process.next_retry.is_some());
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.pid(), pid);
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Backoff);
+        assert!(child_process.next_retry.is_none());
+        assert_ne!(child_process.pid(), pid);
+        Ok(())
+    }
+
     #[test]
     fn test_send_kill_non_blocking() {
         // let mut child_process = /* Initialize your ChildProcess here */;
@@ -805,26 +1479,11 @@ mod tests {
         // Start a long running process
         // Replace with actual long running process
         let program = Program {
-            name: "sleep_fatal".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["100".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
             auto_restart: AutoRestart::Always,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("sleep_fatal", "/bin/sleep 100")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -853,26 +1512,12 @@ mod tests {
         // Start a long running process
         // Replace with actual long running process
         let program = Program {
-            name: "sleep_fatal".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["100".to_string()]),
-            num_procs: 1,
-
-            auto_start: false,
             auto_restart: AutoRestart::Always,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
+            stop_signal: Signal::USR1,
             stop_time: 10,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            ..test_program("sleep_fatal", "/bin/sleep 100")
         };
 
         let mut child_process = ChildProcess::start(&program, 0).unwrap();
@@ -0,0 +1,229 @@
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::model::{Credentials, Error, Program, Result};
+
+// first size tried for the buffers of the getpw*_r and getgr*_r functions
+const LOOKUP_BUFFER: usize = 1024;
+
+// name, uid, primary gid and home directory of a user
+struct User {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: String,
+}
+
+// user by name, or by uid when the name is a number
+fn lookup_user(user: &str) -> Result<User> {
+    let name = CString::new(user).map_err(|_| Error::De(format!("Invalid user {user}")))?;
+    let uid = user.parse::<libc::uid_t>().ok();
+    let mut buf = vec![0 as libc::c_char; LOOKUP_BUFFER];
+    loop {
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found: *mut libc::passwd = std::ptr::null_mut();
+        let ret = unsafe {
+            match uid {
+                Some(uid) => {
+                    libc::getpwuid_r(uid, &mut entry, buf.as_mut_ptr(), buf.len(), &mut found)
+                }
+                None => libc::getpwnam_r(
+                    name.as_ptr(),
+                    &mut entry,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut found,
+                ),
+            }
+        };
+        if ret == libc::ERANGE {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if found.is_null() {
+            return Err(Error::De(format!("Unknown user {user}")));
+        }
+        let string = |ptr: *const libc::c_char| unsafe { CStr::from_ptr(ptr) }.to_string_lossy();
+        return Ok(User {
+            name: string(entry.pw_name).into_owned(),
+            uid: entry.pw_uid,
+            gid: entry.pw_gid,
+            home: string(entry.pw_dir).into_owned(),
+        });
+    }
+}
+
+// gid of a group by name, a number is used as is
+fn lookup_group(group: &str) -> Result<u32> {
+    if let Ok(gid) = group.parse::<libc::gid_t>() {
+        return Ok(gid);
+    }
+    let name = CString::new(group).map_err(|_| Error::De(format!("Invalid group {group}")))?;
+    let mut buf = vec![0 as libc::c_char; LOOKUP_BUFFER];
+    loop {
+        let mut entry: libc::group = unsafe { std::mem::zeroed() };
+        let mut found: *mut libc::group = std::ptr::null_mut();
+        let ret = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut entry,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut found,
+            )
+        };
+        if ret == libc::ERANGE {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if found.is_null() {
+            return Err(Error::De(format!("Unknown group {group}")));
+        }
+        return Ok(entry.gr_gid);
+    }
+}
+
+// groups the user is a member of, like initgroups would set them
+fn user_groups(user: &str, gid: u32) -> Result<Vec<u32>> {
+    let name = CString::new(user).map_err(|_| Error::De(format!("Invalid user {user}")))?;
+    let mut groups: Vec<libc::gid_t> = vec![0; 32];
+    loop {
+        let mut count = groups.len() as libc::c_int;
+        let ret =
+            unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        if ret == -1 {
+            groups.resize((count as usize).max(groups.len() * 2), 0);
+            continue;
+        }
+        groups.truncate(count as usize);
+        return Ok(groups);
+    }
+}
+
+// only root can run processes as someone else, or set their groups
+fn missing_privileges(
+    euid: u32,
+    current: (u32, u32),
+    target: (u32, u32),
+    supplementary_groups: bool,
+) -> bool {
+    euid != 0 && (current != target || supplementary_groups)
+}
+
+impl Program {
+    // None when the processes run as the server. Fails for unknown
+    // users and groups, and when the server can't switch to them
+    pub fn credentials(&self) -> Result<Option<Credentials>> {
+        if self.user.is_none() && self.group.is_none() && self.supplementary_groups.is_empty() {
+            return Ok(None);
+        }
+
+        let user = self.user.as_deref().map(lookup_user).transpose()?;
+        let gid = match (&self.group, &user) {
+            (Some(group), _) => lookup_group(group)?,
+            (None, Some(user)) => user.gid,
+            (None, None) => unsafe { libc::getgid() },
+        };
+        let groups = match &user {
+            _ if !self.supplementary_groups.is_empty() => {
+                let mut groups = vec![];
+                for group in &self.supplementary_groups {
+                    groups.push(lookup_group(group)?);
+                }
+                groups
+            }
+            Some(user) => user_groups(&user.name, gid)?,
+            None => vec![],
+        };
+        let credentials = Credentials {
+            uid: user
+                .as_ref()
+                .map_or_else(|| unsafe { libc::getuid() }, |user| user.uid),
+            gid,
+            groups,
+            user: user.map(|user| (user.name, user.home)),
+        };
+
+        let current = unsafe { (libc::getuid(), libc::getgid()) };
+        if missing_privileges(
+            unsafe { libc::geteuid() },
+            current,
+            (credentials.uid, credentials.gid),
+            !self.supplementary_groups.is_empty(),
+        ) {
+            return Err(Error::Privileges(format!(
+                "Program {} runs as uid {} gid {}, the server has to run as root",
+                self.name, credentials.uid, credentials.gid
+            )));
+        }
+        Ok(Some(credentials))
+    }
+}
+
+impl Credentials {
+    // switch to the user and groups in the child before exec, the
+    // groups first as they can't be changed anymore once not root
+    pub fn apply(&self, command: &mut Command) {
+        if let Some((name, home)) = &self.user {
+            command
+                .env("USER", name)
+                .env("LOGNAME", name)
+                .env("HOME", home);
+        }
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+
+        let Credentials {
+            uid, gid, groups, ..
+        } = self.clone();
+        // only async signal safe calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() -> Result<()> {
+        let root = lookup_user("root")?;
+        assert_eq!((root.uid, root.gid), (0, 0));
+        assert_eq!(lookup_user("0")?.name, "root");
+        assert_eq!(lookup_group("root")?, 0);
+        assert_eq!(lookup_group("4242")?, 4242);
+        assert!(matches!(
+            lookup_user("taskmaster-no-such-user"),
+            Err(Error::De(_))
+        ));
+        assert!(matches!(
+            lookup_group("taskmaster-no-such-group"),
+            Err(Error::De(_))
+        ));
+        assert!(user_groups(&root.name, root.gid)?.contains(&0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_privileges() {
+        assert!(!missing_privileges(0, (0, 0), (1000, 1000), true));
+        // already the user of the server
+        assert!(!missing_privileges(1000, (1000, 1000), (1000, 1000), false));
+        assert!(missing_privileges(1001, (1001, 1001), (1000, 1000), false));
+        assert!(missing_privileges(1000, (1000, 1000), (1000, 100), false));
+        assert!(missing_privileges(1000, (1000, 1000), (1000, 1000), true));
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use crate::model::{Cmd, Error, Program, Result};
+
+// `${VAR}` from the environment of the daemon, `${VAR:-default}` gives
+// the default when the variable is unset or empty
+fn lookup_var(spec: &str) -> Result<String> {
+    let (name, default) = match spec.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (spec, None),
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(Error::Expansion(format!("Invalid variable name '{name}'")));
+    }
+    match (std::env::var(name), default) {
+        (Ok(value), Some(default)) if value.is_empty() => Ok(default.to_string()),
+        (Ok(value), _) => Ok(value),
+        (Err(_), Some(default)) => Ok(default.to_string()),
+        (Err(_), None) => Err(Error::Expansion(format!("Undefined variable {name}"))),
+    }
+}
+
+// `%(key)s` or `%(key)d` with an optional width, `%(process_num)02d`
+// pads the number with zeros like supervisord does
+fn format_builtin(
+    key: &str,
+    width: &str,
+    conversion: Option<char>,
+    program_name: &str,
+    process_num: u8,
+) -> Result<String> {
+    let zero = width.starts_with('0');
+    let width = match width {
+        "" => 0,
+        width => width
+            .parse::<usize>()
+            .map_err(|e| Error::Expansion(format!("Invalid width in %({key}) : {e}")))?,
+    };
+    match (key, conversion) {
+        ("program_name", Some('s')) => Ok(format!("{program_name:>width$}")),
+        ("process_num", Some('d')) if zero => Ok(format!("{process_num:0width$}")),
+        ("process_num", Some('d' | 's')) => Ok(format!("{process_num:>width$}")),
+        _ => Err(Error::Expansion(format!(
+            "Unknown expansion %({key}){width}{}",
+            conversion.map(String::from).unwrap_or_default()
+        ))),
+    }
+}
+
+// expand the variables and built-ins of a config value. `$${` and `%%`
+// give a literal `${` and `%`, any other `$` or `%` is kept as is so
+// shell lines can still use `$HOME` or `date +%s`
+pub(crate) fn expand(value: &str, program_name: &str, process_num: u8) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(index) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            expanded.push_str("${");
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let Some(end) = after.find('}') else {
+                return Err(Error::Expansion(format!(
+                    "Unterminated variable in '{value}'"
+                )));
+            };
+            expanded.push_str(&lookup_var(&after[..end])?);
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix("%%") {
+            expanded.push('%');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("%(") {
+            let Some(end) = after.find(')') else {
+                return Err(Error::Expansion(format!(
+                    "Unterminated expansion in '{value}'"
+                )));
+            };
+            let key = &after[..end];
+            let spec = &after[end + 1..];
+            let (width, spec) = spec.split_at(
+                spec.find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(spec.len()),
+            );
+            let conversion = spec.chars().next();
+            expanded.push_str(&format_builtin(
+                key,
+                width,
+                conversion,
+                program_name,
+                process_num,
+            )?);
+            rest = &spec[conversion.map_or(0, char::len_utf8)..];
+        } else {
+            // a lone `$` or `%`, both are one byte long
+            expanded.push_str(&rest[..1]);
+            rest = &rest[1..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+impl Program {
+    // copy of the program with the values used to start one of its
+    // processes expanded, the words of the command are expanded one by one
+    // so a value with blanks stays a single argument
+    pub fn expanded(&self, process_num: u8) -> Result<Program> {
+        let expand = |value: &str| expand(value, &self.name, process_num);
+
+        let cmd = Cmd {
+            program: expand(&self.cmd.program)?,
+            args: self
+                .cmd
+                .args
+                .iter()
+                .map(|arg| expand(arg))
+                .collect::<Result<Vec<_>>>()?,
+            line: expand(&self.cmd.line)?,
+        };
+        let env = match &self.env {
+            Some(env) => {
+                let mut expanded = HashMap::new();
+                for (key, value) in env {
+                    expanded.insert(key.clone(), expand(value)?);
+                }
+                Some(expanded)
+            }
+            None => None,
+        };
+
+        Ok(Program {
+            cmd,
+            env,
+            working_dir: expand(&self.working_dir)?,
+            stdout: expand(&self.stdout)?,
+            stderr: expand(&self.stderr)?,
+            children: vec![],
+            ..self.clone()
+        })
+    }
+
+    // fails when a value does not expand, or when two outputs of the
+    // processes would truncate or rotate the same file. Devices like
+    // /dev/null can be shared, files too in append mode without rotation
+    pub fn check_log_paths(&self) -> Result<()> {
+        // the built-ins only change the values, not whether they expand
+        let expanded = self.expanded(0)?;
+        if self.append && self.rotation.is_none() {
+            return Ok(());
+        }
+
+        let mut paths = vec![];
+        for process_num in 0..self.num_procs {
+            let expanded = match process_num {
+                0 => expanded.clone(),
+                _ => self.expanded(process_num)?,
+            };
+            for path in [expanded.stdout, expanded.stderr] {
+                if path.starts_with("/dev/") {
+                    continue;
+                }
+                if paths.contains(&path) {
+                    return Err(Error::De(format!(
+                        "Program {} writes several outputs to {path}, use %(process_num)d in the path or set append without rotation",
+                        self.name
+                    )));
+                }
+                paths.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Rotation;
+
+    #[test]
+    fn test_expand_builtins() -> Result<()> {
+        assert_eq!(
+            expand("/var/log/%(program_name)s-%(process_num)d.log", "web", 3)?,
+            "/var/log/web-3.log"
+        );
+        assert_eq!(expand("port 80%(process_num)02d", "web", 7)?, "port 8007");
+        assert_eq!(
+            expand("100%% of %(program_name)5s", "web", 0)?,
+            "100% of   web"
+        );
+        assert_eq!(
+            expand("date +%s; echo $HOME $1", "web", 0)?,
+            "date +%s; echo $HOME $1"
+        );
+        assert!(expand("%(group_name)s", "web", 0).is_err());
+        assert!(expand("%(program_name)d", "web", 0).is_err());
+        assert!(expand("%(program_name", "web", 0).is_err());
+        Ok(())
+    }
+
+    // the tests run in parallel in one process, every variable is only
+    // set and read by a single test
+    #[test]
+    fn test_expand_env() -> Result<()> {
+        std::env::set_var("TASKMASTER_TEST_EXPAND", "/srv");
+        std::env::set_var("TASKMASTER_TEST_EXPAND_EMPTY", "");
+        assert_eq!(
+            expand("${TASKMASTER_TEST_EXPAND}/data", "web", 0)?,
+            "/srv/data"
+        );
+        assert_eq!(
+            expand("${TASKMASTER_TEST_EXPAND:-/tmp}/data", "web", 0)?,
+            "/srv/data"
+        );
+        assert_eq!(
+            expand("${TASKMASTER_TEST_EXPAND_EMPTY:-/tmp}", "web", 0)?,
+            "/tmp"
+        );
+        assert_eq!(expand("${TASKMASTER_TEST_UNSET:-}", "web", 0)?, "");
+        assert_eq!(expand("$${HOME}", "web", 0)?, "${HOME}");
+        assert!(expand("${TASKMASTER_TEST_UNSET}", "web", 0).is_err());
+        assert!(expand("${TASKMASTER_TEST_EXPAND", "web", 0).is_err());
+        assert!(expand("${}", "web", 0).is_err());
+        std::env::remove_var("TASKMASTER_TEST_EXPAND");
+        std::env::remove_var("TASKMASTER_TEST_EXPAND_EMPTY");
+        Ok(())
+    }
+
+    #[test]
+    fn test_program_expanded() -> Result<()> {
+        std::env::set_var("TASKMASTER_TEST_EXPANDED_ROOT", "/srv/app root");
+        let yaml = r#"
+        cmd: "/bin/server --root ${TASKMASTER_TEST_EXPANDED_ROOT} --port 80%(process_num)02d"
+        numprocs: 4
+        autostart: true
+        autorestart: "never"
+        exitcodes: [0]
+        startretries: 0
+        startsecs: 0
+        stopsignal: "TERM"
+        stoptime: 1
+        env:
+          INSTANCE: "%(program_name)s-%(process_num)d"
+        workingdir: "${TASKMASTER_TEST_EXPANDED_ROOT}"
+        stdout: "/tmp/%(program_name)s-%(process_num)d.out"
+        stderr: "/tmp/%(program_name)s-%(process_num)d.err"
+        "#;
+        let mut program: Program =
+            serde_yaml::from_str(yaml).map_err(|e| Error::De(e.to_string()))?;
+        program.name = "web".to_string();
+
+        let expanded = program.expanded(2)?;
+        assert_eq!(
+            expanded.cmd.args,
+            vec!["--root", "/srv/app root", "--port", "8002"]
+        );
+        assert_eq!(expanded.working_dir, "/srv/app root");
+        assert_eq!(expanded.stdout, "/tmp/web-2.out");
+        assert_eq!(expanded.stderr, "/tmp/web-2.err");
+        assert_eq!(expanded.env.unwrap()["INSTANCE"], "web-2");
+        // the config itself is left untouched for reloads
+        assert_eq!(program.stdout, "/tmp/%(program_name)s-%(process_num)d.out");
+        std::env::remove_var("TASKMASTER_TEST_EXPANDED_ROOT");
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_log_paths() -> Result<()> {
+        let program = |stdout: &str, stderr: &str, append: bool| -> Result<Program> {
+            let yaml = format!(
+                r#"
+        cmd: "/bin/true"
+        numprocs: 2
+        autostart: true
+        autorestart: "never"
+        exitcodes: [0]
+        startretries: 0
+        startsecs: 0
+        stopsignal: "TERM"
+        stoptime: 1
+        workingdir: "/tmp"
+        stdout: "{stdout}"
+        stderr: "{stderr}"
+        append: {append}
+        "#
+            );
+            serde_yaml::from_str(&yaml).map_err(|e| Error::De(e.to_string()))
+        };
+
+        program("/tmp/web-%(process_num)d.out", "/dev/null", false)?.check_log_paths()?;
+        program("/dev/null", "/dev/null", false)?.check_log_paths()?;
+        program("/tmp/web.log", "/tmp/web.log", true)?.check_log_paths()?;
+        // both would rotate the same file
+        let mut rotated = program("/tmp/web.log", "/tmp/web.log", true)?;
+        rotated.rotation = Some(Rotation {
+            maxbytes: 1024,
+            backups: 1,
+            compress: false,
+        });
+        assert!(rotated.check_log_paths().is_err());
+        // both processes truncate the same file
+        assert!(program("/tmp/web.out", "/dev/null", false)?
+            .check_log_paths()
+            .is_err());
+        // stdout and stderr of a process truncate the same file
+        assert!(program(
+            "/tmp/web-%(process_num)d.log",
+            "/tmp/web-%(process_num)d.log",
+            false
+        )?
+        .check_log_paths()
+        .is_err());
+        assert!(program("${TASKMASTER_TEST_LOG_UNSET}", "/dev/null", true)?
+            .check_log_paths()
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use logger::{log, LogInfo};
+
+use crate::model::{ChildProcess, Probe, Program, ProgramState, Result};
+
+const PROBE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn localhost(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+// the command has to exit with 0 before the timeout
+fn probe_cmd(cmd: &(String, Vec<String>), timeout: Duration) -> bool {
+    let child = Command::new(&cmd.0)
+        .args(&cmd.1)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return false;
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) if Instant::now() < deadline => thread::sleep(PROBE_POLL_INTERVAL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return false;
+            }
+        }
+    }
+}
+
+// the response has to start with a 2xx status line
+fn probe_http(port: u16, path: &str, timeout: Duration) -> bool {
+    let request = || -> std::io::Result<bool> {
+        let mut stream = TcpStream::connect_timeout(&localhost(port), timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.0\r\nHost: localhost:{port}\r\nConnection: close\r\n\r\n"
+        )?;
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        Ok(status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+            .is_some_and(|code| (200..300).contains(&code)))
+    };
+    request().unwrap_or(false)
+}
+
+impl Probe {
+    // blocks up to the timeout, true if the process is healthy
+    pub fn run(&self, timeout: Duration) -> bool {
+        match self {
+            Probe::Cmd(cmd) => probe_cmd(cmd, timeout),
+            Probe::Tcp(port) => TcpStream::connect_timeout(&localhost(*port), timeout).is_ok(),
+            Probe::Http { port, path } => probe_http(*port, path, timeout),
+        }
+    }
+}
+
+impl ChildProcess {
+    // called while the process is running, collects the result of the last
+    // probe and starts the next one when it is due. The probes run in their
+    // own thread to not block the server loop. An unhealthy process with
+    // `restart` set is asked to restart
+    pub fn check_health(&mut self, config: &Program, process_number: u8) -> Result<()> {
+        let Some(healthcheck) = &config.healthcheck else {
+            return Ok(());
+        };
+
+        let result = self
+            .health
+            .probe
+            .as_ref()
+            .and_then(|probe| probe.get().copied());
+        match result {
+            Some(true) => {
+                self.health.probe = None;
+                self.health.failures = 0;
+                if self.state == ProgramState::Unhealthy {
+                    let _ = log(
+                        format!(
+                            "{}--{}: From unhealthy to running\n",
+                            config.name, process_number
+                        ),
+                        LogInfo::Info,
+                    );
+                    self.state = ProgramState::Running;
+                }
+            }
+            Some(false) => {
+                self.health.probe = None;
+                self.health.failures = self.health.failures.saturating_add(1);
+                if self.state == ProgramState::Running
+                    && self.health.failures >= healthcheck.failure_threshold
+                {
+                    let _ = log(
+                        format!(
+                            "{}--{}: From running to unhealthy after {} failed health checks\n",
+                            config.name, process_number, self.health.failures
+                        ),
+                        LogInfo::Warn,
+                    );
+                    self.state = ProgramState::Unhealthy;
+                    if healthcheck.restart {
+                        let _ = log(
+                            format!(
+                                "{}--{}: Stopping unhealthy process to restart it\n",
+                                config.name, process_number
+                            ),
+                            LogInfo::Info,
+                        );
+                        return self.request_restart(config);
+                    }
+                }
+            }
+            // the probe is still running
+            None if self.health.probe.is_some() => return Ok(()),
+            None => (),
+        }
+
+        if self
+            .health
+            .next_check
+            .is_none_or(|next_check| Instant::now() >= next_check)
+        {
+            let probe = Arc::new(OnceLock::new());
+            let result = Arc::clone(&probe);
+            let check = healthcheck.probe.clone();
+            let timeout = Duration::from_secs(healthcheck.timeout.into());
+            thread::spawn(move || result.set(check.run(timeout)));
+            self.health.probe = Some(probe);
+            self.health.next_check =
+                Some(Instant::now() + Duration::from_secs(healthcheck.interval.into()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_probe_cmd() {
+        let timeout = Duration::from_secs(1);
+        assert!(Probe::Cmd(("/bin/true".to_string(), vec![])).run(timeout));
+        assert!(!Probe::Cmd(("/bin/false".to_string(), vec![])).run(timeout));
+        assert!(!Probe::Cmd(("/does/not/exist".to_string(), vec![])).run(timeout));
+
+        // killed after the timeout
+        let start = Instant::now();
+        assert!(
+            !Probe::Cmd(("/bin/sleep".to_string(), vec!["5".to_string()]))
+                .run(Duration::from_millis(200))
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_probe_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(Probe::Tcp(port).run(Duration::from_secs(1)));
+        drop(listener);
+        assert!(!Probe::Tcp(port).run(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_probe_http() {
+        let serve = |status: &'static str| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                // request line and headers
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let mut stream = stream;
+                write!(stream, "HTTP/1.0 {status}\r\n\r\n").unwrap();
+            });
+            port
+        };
+        let timeout = Duration::from_secs(1);
+
+        let port = serve("200 OK");
+        assert!(Probe::Http {
+            port,
+            path: "/health".to_string()
+        }
+        .run(timeout));
+
+        let port = serve("503 Service Unavailable");
+        assert!(!Probe::Http {
+            port,
+            path: "/".to_string()
+        }
+        .run(timeout));
+    }
+}
@@ -0,0 +1,121 @@
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::model::{Error, Limit, Limits, Program, Result, UNLIMITED};
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type Resource = libc::c_int;
+
+// resource of a limit, by its name in the config
+fn resource(name: &str) -> Resource {
+    match name {
+        "nofile" => libc::RLIMIT_NOFILE,
+        "nproc" => libc::RLIMIT_NPROC,
+        "core" => libc::RLIMIT_CORE,
+        "as" => libc::RLIMIT_AS,
+        "cpu" => libc::RLIMIT_CPU,
+        _ => libc::RLIMIT_STACK,
+    }
+}
+
+fn rlimit(limit: Limit) -> libc::rlimit {
+    let value = |value: u64| match value {
+        UNLIMITED => libc::RLIM_INFINITY,
+        value => value,
+    };
+    libc::rlimit {
+        rlim_cur: value(limit.soft),
+        rlim_max: value(limit.hard),
+    }
+}
+
+// only root can raise a hard limit
+fn missing_privileges(euid: u32, limit: Limit, current_hard: u64) -> bool {
+    euid != 0 && limit.hard > current_hard
+}
+
+impl Program {
+    // fails when a hard limit is above the one of the server, and the
+    // server can't raise it
+    pub fn check_limits(&self) -> Result<()> {
+        let euid = unsafe { libc::geteuid() };
+        for (name, limit) in self.limits.entries() {
+            let mut current: libc::rlimit = unsafe { std::mem::zeroed() };
+            if unsafe { libc::getrlimit(resource(name), &mut current) } != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let current_hard = match current.rlim_max {
+                libc::RLIM_INFINITY => UNLIMITED,
+                value => value,
+            };
+            if missing_privileges(euid, limit, current_hard) {
+                let hard = Limit {
+                    soft: limit.hard,
+                    ..limit
+                };
+                return Err(Error::Privileges(format!(
+                    "Program {} raises the hard {name} limit to {hard}, the server has to run as root",
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Limits {
+    // set the limits in the child before exec, has to come before the
+    // switch to another user to still be allowed to raise them
+    pub fn apply(&self, command: &mut Command) {
+        let limits = self
+            .entries()
+            .into_iter()
+            .map(|(name, limit)| (resource(name), rlimit(limit)))
+            .collect::<Vec<_>>();
+        if limits.is_empty() {
+            return;
+        }
+
+        // only async signal safe calls between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, limit) in &limits {
+                    if libc::setrlimit(*resource, limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_privileges() {
+        let limit = Limit {
+            soft: 1024,
+            hard: 4096,
+        };
+        assert!(!missing_privileges(0, limit, 1024));
+        assert!(!missing_privileges(1000, limit, 4096));
+        assert!(!missing_privileges(1000, limit, UNLIMITED));
+        assert!(missing_privileges(1000, limit, 2048));
+    }
+
+    #[test]
+    fn test_rlimit() {
+        let limit = rlimit(Limit {
+            soft: 0,
+            hard: UNLIMITED,
+        });
+        assert_eq!(limit.rlim_cur, 0);
+        assert_eq!(limit.rlim_max, libc::RLIM_INFINITY);
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use logger::{log, LogInfo};
+
+use crate::model::{LogFile, Result, Rotation};
+
+// the server runs with umask 0, the mode is never widened by it
+const LOG_MODE: u32 = 0o644;
+
+// output file of a process, truncated unless in append mode
+pub(crate) fn open_log(path: &Path, append: bool) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .mode(LOG_MODE)
+        .open(path)?;
+    Ok(file)
+}
+
+// empty file, replaces the existing one
+fn create(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(LOG_MODE)
+        .open(path)
+}
+
+// gzip `from` into `to`, `from` is removed once done
+fn compress(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(create(to)?, Compression::default());
+    io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(from)
+}
+
+impl LogFile {
+    pub fn open(path: &str, append: bool, rotation: Rotation) -> Result<LogFile> {
+        let path = PathBuf::from(path);
+        let file = open_log(&path, append)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile {
+            path,
+            file,
+            size,
+            rotation,
+            compressing: None,
+        })
+    }
+
+    // `<path>.<index>`, with `.gz` when the backups are compressed
+    fn backup(&self, index: u8) -> PathBuf {
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(format!(".{index}"));
+        if self.rotation.compress {
+            backup.push(".gz");
+        }
+        PathBuf::from(backup)
+    }
+
+    // the backups can only move once the previous one is compressed
+    fn wait_compression(&mut self) {
+        if let Some(compressing) = self.compressing.take() {
+            let _ = compressing.join();
+        }
+    }
+
+    // every backup moves one index up, the oldest one is overwritten. The
+    // first one is compressed in its own thread, the output of the process
+    // is still forwarded meanwhile
+    fn rotate(&mut self) -> io::Result<()> {
+        self.wait_compression();
+        if self.rotation.backups > 0 {
+            for index in (1..self.rotation.backups).rev() {
+                let backup = self.backup(index);
+                if backup.exists() {
+                    fs::rename(&backup, self.backup(index + 1))?;
+                }
+            }
+            let mut first = self.path.clone().into_os_string();
+            first.push(".1");
+            fs::rename(&self.path, &first)?;
+            if self.rotation.compress {
+                let to = self.backup(1);
+                self.compressing = Some(thread::spawn(move || {
+                    if let Err(e) = compress(Path::new(&first), &to) {
+                        let _ = log(
+                            format!("Failed to compress {}: {}\n", to.display(), e),
+                            LogInfo::Error,
+                        );
+                    }
+                }));
+            }
+        }
+        self.file = create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.rotation.maxbytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    // copy the output of the process until it is closed. On a write error
+    // the output is still read, a full pipe would block the process
+    pub fn forward<R: Read + Send + 'static>(mut self, mut output: R) {
+        thread::spawn(move || {
+            let mut buf = [0; 8192];
+            let mut failed = false;
+            loop {
+                match output.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) if !failed => {
+                        if let Err(e) = self.write(&buf[..len]) {
+                            let _ = log(
+                                format!("Failed to write to {}: {}\n", self.path.display(), e),
+                                LogInfo::Error,
+                            );
+                            failed = true;
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("taskmaster-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_rotate() -> Result<()> {
+        let dir = temp_dir("rotate");
+        let path = dir.join("out.log");
+        let rotation = Rotation {
+            maxbytes: 10,
+            backups: 2,
+            compress: false,
+        };
+        let mut log_file = LogFile::open(&path.display().to_string(), false, rotation)?;
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            log_file.write(line.as_bytes())?;
+        }
+
+        assert_eq!(fs::read_to_string(&path)?, "fourth\n");
+        assert_eq!(fs::read_to_string(dir.join("out.log.1"))?, "third\n");
+        assert_eq!(fs::read_to_string(dir.join("out.log.2"))?, "second\n");
+        // only `backups` files are kept
+        assert!(!dir.join("out.log.3").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_compress() -> Result<()> {
+        let dir = temp_dir("rotate-compress");
+        let path = dir.join("out.log");
+        let rotation = Rotation {
+            maxbytes: 10,
+            backups: 1,
+            compress: true,
+        };
+        let mut log_file = LogFile::open(&path.display().to_string(), false, rotation)?;
+        log_file.write(b"first line\n")?;
+        log_file.write(b"second line\n")?;
+        log_file.wait_compression();
+
+        let mut backup = String::new();
+        GzDecoder::new(File::open(dir.join("out.log.1.gz"))?).read_to_string(&mut backup)?;
+        assert_eq!(backup, "first line\n");
+        assert!(!dir.join("out.log.1").exists());
+        assert_eq!(fs::read_to_string(&path)?, "second line\n");
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_without_backups() -> Result<()> {
+        let dir = temp_dir("rotate-no-backup");
+        let path = dir.join("out.log");
+        fs::create_dir_all(&dir)?;
+        fs::write(&path, "previous run\n")?;
+        let rotation = Rotation {
+            maxbytes: 16,
+            backups: 0,
+            compress: false,
+        };
+        // the size of the appended file counts
+        let mut log_file = LogFile::open(&path.display().to_string(), true, rotation)?;
+        log_file.write(b"new run\n")?;
+
+        assert_eq!(fs::read_to_string(&path)?, "new run\n");
+        assert!(!dir.join("out.log.1").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,16 @@
+mod backoff;
+mod cgroup;
 mod childprocess;
+mod credentials;
+mod expand;
+mod healthcheck;
+mod limits;
+mod logfile;
+mod notify;
+mod planner;
 mod program;
 mod programs;
+mod tail;
+mod watchdog;
 
 pub use childprocess::*;
-pub use program::*;
-pub use programs::*;
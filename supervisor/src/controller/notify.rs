@@ -0,0 +1,175 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use std::time::Instant;
+
+use crate::model::{ChildProcess, Credentials, NotifySocket, Result};
+
+// every socket gets its own path
+static SOCKET_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+impl NotifySocket {
+    // only the user the process runs as can send on it, `owner` when it
+    // switches to another one. The socket is bound in a directory only the
+    // server can enter, which is given to the owner once the socket is
+    // restricted
+    pub fn bind(owner: Option<&Credentials>) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "taskmaster-notify-{}-{}",
+            std::process::id(),
+            SOCKET_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // left by a previous server with the same pid
+        let _ = fs::remove_dir_all(&dir);
+        fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let socket = match UnixDatagram::bind(dir.join("notify.sock")) {
+            Ok(socket) => socket,
+            Err(e) => {
+                let _ = fs::remove_dir(&dir);
+                return Err(e.into());
+            }
+        };
+        // removed with its directory on error
+        let notify = NotifySocket {
+            socket,
+            path: dir.join("notify.sock"),
+        };
+        notify.socket.set_nonblocking(true)?;
+        fs::set_permissions(&notify.path, fs::Permissions::from_mode(0o600))?;
+        if let Some(owner) = owner {
+            std::os::unix::fs::chown(&notify.path, Some(owner.uid), Some(owner.gid))?;
+            std::os::unix::fs::chown(&dir, Some(owner.uid), Some(owner.gid))?;
+        }
+        Ok(notify)
+    }
+
+    // every `KEY=VALUE` line sent by the process since the last call
+    pub fn receive(&self) -> Vec<String> {
+        let mut messages = vec![];
+        let mut buf = [0; 4096];
+        loop {
+            match self.socket.recv(&mut buf) {
+                Ok(len) => messages.extend(
+                    String::from_utf8_lossy(&buf[..len])
+                        .lines()
+                        .filter(|line| !line.is_empty())
+                        .map(String::from),
+                ),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // WouldBlock once everything has been read
+                Err(_) => break,
+            }
+        }
+        messages
+    }
+}
+
+impl ChildProcess {
+    // read what the process sent since the last check
+    pub fn receive_notifications(&mut self) {
+        let Some(socket) = &self.notify.socket else {
+            return;
+        };
+        for message in socket.receive() {
+            match message.split_once('=') {
+                Some(("READY", "1")) => self.notify.ready = true,
+                Some(("STATUS", status)) => self.notify.status = Some(status.to_string()),
+                Some(("WATCHDOG", "1")) => self.notify.heartbeat = Some(Instant::now()),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Drop for NotifySocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        if let Some(dir) = self.path.parent() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{test_program, ChildExitStatus, Program, ProgramState};
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn test_receive() -> Result<()> {
+        let notify = NotifySocket::bind(None)?;
+        assert!(notify.receive().is_empty());
+
+        let client = UnixDatagram::unbound()?;
+        client.send_to(b"READY=1\nSTATUS=up", &notify.path)?;
+        client.send_to(b"WATCHDOG=1", &notify.path)?;
+        assert_eq!(notify.receive(), vec!["READY=1", "STATUS=up", "WATCHDOG=1"]);
+        assert!(notify.receive().is_empty());
+
+        let path = notify.path.clone();
+        drop(notify);
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_owner() -> Result<()> {
+        // giving the socket to another user needs root
+        if unsafe { libc::geteuid() } != 0 {
+            return Ok(());
+        }
+        let nobody = Credentials {
+            uid: 65534,
+            gid: 65534,
+            groups: vec![],
+            user: None,
+        };
+        let notify = NotifySocket::bind(Some(&nobody))?;
+        let metadata = fs::metadata(&notify.path)?;
+        assert_eq!(metadata.uid(), 65534);
+        assert_eq!(metadata.gid(), 65534);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        // only the owner can reach the socket
+        let metadata = fs::metadata(notify.path.parent().unwrap())?;
+        assert_eq!(metadata.uid(), 65534);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o700);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ready() -> Result<()> {
+        let program = Program {
+            notify: true,
+            // exits right away without the socket
+            ..test_program(
+                "notify",
+                "/bin/sh -c 'test -S \"$NOTIFY_SOCKET\" && exec sleep 10'",
+            )
+        };
+        let mut child_process = ChildProcess::start(&program, 0)?;
+        let path = child_process.notify.socket.as_ref().unwrap().path.clone();
+
+        // startsecs are over but it is not ready yet
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.exit_status, ChildExitStatus::Running);
+        assert_eq!(child_process.state, ProgramState::Starting);
+
+        let client = UnixDatagram::unbound()?;
+        client.send_to(b"STATUS=Listening on 8080\nREADY=1", &path)?;
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
+        assert_eq!(
+            child_process.status(0).status_text.as_deref(),
+            Some("Listening on 8080")
+        );
+
+        child_process.kill_program();
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use crate::model::{Error, Program, Result};
+
+// depth first walk of the dependencies of a program, `path` is the chain
+// of programs being visited, a program found twice in it is a cycle
+fn visit(
+    name: &str,
+    programs: &HashMap<String, Program>,
+    visited: &mut Vec<String>,
+    path: &mut Vec<String>,
+) -> Result<()> {
+    if visited.iter().any(|v| v == name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|p| p == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name.to_string());
+        return Err(Error::DependencyCycle(cycle));
+    }
+
+    path.push(name.to_string());
+    for dependency in &programs[name].depends_on {
+        if !programs.contains_key(dependency) {
+            return Err(Error::De(format!(
+                "Program {name} depends on unknown program {dependency}"
+            )));
+        }
+        visit(dependency, programs, visited, path)?;
+    }
+    path.pop();
+
+    visited.push(name.to_string());
+    Ok(())
+}
+
+// fails on unknown dependencies and cycles
+fn check_dependencies(programs: &HashMap<String, Program>) -> Result<()> {
+    let mut names = programs.keys().collect::<Vec<_>>();
+    // always report the same cycle
+    names.sort();
+
+    let mut visited = vec![];
+    for name in names {
+        visit(name, programs, &mut visited, &mut vec![])?;
+    }
+    Ok(())
+}
+
+// every program comes after its dependencies, among the programs that
+// can be started, the lowest priority comes first, then the name
+pub(crate) fn start_order(programs: &HashMap<String, Program>) -> Result<Vec<String>> {
+    check_dependencies(programs)?;
+
+    let mut remaining = programs.keys().cloned().collect::<Vec<_>>();
+    let mut order: Vec<String> = vec![];
+    while !remaining.is_empty() {
+        // there is always one, the graph has no cycle
+        let (index, _) = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| programs[*name].depends_on.iter().all(|d| order.contains(d)))
+            .min_by_key(|(_, name)| (programs[*name].priority, *name))
+            .expect("no program can be started");
+        order.push(remaining.swap_remove(index));
+    }
+    Ok(order)
+}
+
+// programs grouped by the steps of the shutdown, every program is stopped
+// before its dependencies. Among the programs that can be stopped, the ones
+// with the highest priority make the next step and are stopped together
+pub(crate) fn stop_steps(programs: &HashMap<String, Program>) -> Result<Vec<Vec<String>>> {
+    check_dependencies(programs)?;
+
+    let mut remaining = programs.keys().cloned().collect::<Vec<_>>();
+    let mut steps = vec![];
+    while !remaining.is_empty() {
+        let needed = |name: &String| {
+            remaining
+                .iter()
+                .any(|other| programs[other].depends_on.contains(name))
+        };
+        let ready = remaining
+            .iter()
+            .filter(|name| !needed(name))
+            .collect::<Vec<_>>();
+        // there is always one, the graph has no cycle
+        let priority = ready
+            .iter()
+            .map(|name| programs[*name].priority)
+            .max()
+            .expect("no program can be stopped");
+        let mut step = ready
+            .into_iter()
+            .filter(|name| programs[*name].priority == priority)
+            .cloned()
+            .collect::<Vec<_>>();
+        step.sort();
+        remaining.retain(|name| !step.contains(name));
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{test_program, DEFAULT_PRIORITY};
+
+    fn program(name: &str, depends_on: &[&str]) -> (String, Program) {
+        with_priority(name, depends_on, DEFAULT_PRIORITY)
+    }
+
+    fn with_priority(name: &str, depends_on: &[&str], priority: u16) -> (String, Program) {
+        (
+            name.to_string(),
+            Program {
+                auto_start: true,
+                working_dir: "/tmp".to_string(),
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                priority,
+                ..test_program(name, "/bin/true")
+            },
+        )
+    }
+
+    #[test]
+    fn test_start_order() -> Result<()> {
+        let programs = HashMap::from([
+            program("web", &["db", "cache"]),
+            program("worker", &["web"]),
+            program("db", &[]),
+            program("cache", &["db"]),
+            program("cron", &[]),
+        ]);
+        assert_eq!(
+            start_order(&programs)?,
+            vec!["cron", "db", "cache", "web", "worker"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_steps() -> Result<()> {
+        let programs = HashMap::from([
+            program("web", &["db"]),
+            program("worker", &["db"]),
+            program("db", &[]),
+            program("cron", &[]),
+        ]);
+        assert_eq!(
+            stop_steps(&programs)?,
+            vec![vec!["cron", "web", "worker"], vec!["db"]]
+        );
+        assert!(stop_steps(&HashMap::new())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority() -> Result<()> {
+        let programs = HashMap::from([
+            with_priority("web", &["db"], 1),
+            with_priority("db", &[], 50),
+            with_priority("cron", &[], 10),
+            with_priority("metrics", &[], 100),
+        ]);
+        // the dependencies come first whatever their priority
+        assert_eq!(
+            start_order(&programs)?,
+            vec!["cron", "db", "web", "metrics"]
+        );
+        assert_eq!(
+            stop_steps(&programs)?,
+            vec![vec!["metrics"], vec!["cron"], vec!["web"], vec!["db"]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle() {
+        let programs = HashMap::from([
+            program("a", &["b"]),
+            program("b", &["c"]),
+            program("c", &["a"]),
+            program("d", &["a"]),
+        ]);
+        match start_order(&programs) {
+            Err(Error::DependencyCycle(cycle)) => assert_eq!(cycle, vec!["a", "b", "c", "a"]),
+            _ => panic!("expected a dependency cycle"),
+        }
+
+        let programs = HashMap::from([program("a", &["a"])]);
+        assert!(matches!(
+            start_order(&programs),
+            Err(Error::DependencyCycle(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_dependency() {
+        let programs = HashMap::from([program("web", &["db"])]);
+        assert!(matches!(start_order(&programs), Err(Error::De(_))));
+    }
+}
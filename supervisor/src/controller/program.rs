@@ -1,4 +1,5 @@
-use std::time::Instant;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use logger::{log, LogInfo};
 
@@ -6,11 +7,35 @@ use crate::model::ChildProcess;
 use crate::model::Origin;
 use crate::model::Program;
 use crate::model::ProgramState;
+use crate::model::ProgramStatus;
 use crate::model::Result;
 use crate::ChildExitStatus;
 use crate::Error;
+use crate::GroupSignals;
+use crate::Health;
+use crate::Notify;
 
-// use logger::{log, LogInfo};
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// no short circuit, every child has to be checked
+fn finish_stop(children: &mut [ChildProcess], stop_time: u16) -> bool {
+    let mut done = true;
+    for child_process in children.iter_mut() {
+        done &= child_process.finish_stop(stop_time);
+    }
+    done
+}
+
+fn stop_and_wait(children: &mut [ChildProcess], stop_signal: u8, stop_time: u16) {
+    for child_process in children.iter_mut() {
+        if let Err(e) = child_process.stop(stop_signal) {
+            let _ = log(format!("Failed to stop process: {}\n", e), LogInfo::Error);
+        }
+    }
+    while !finish_stop(children, stop_time) {
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+}
 
 impl Program {
     pub fn check(&mut self) -> Result<()> {
@@ -33,6 +58,35 @@ impl Program {
         Ok(())
     }
 
+    // only run the state machine of the children that have exited,
+    // called when the server receives a SIGCHLD
+    pub fn reap_children(&mut self) -> Result<()> {
+        let self_clone = self.clone();
+        let mut reaped = false;
+
+        for (index, child_process) in self.children.iter_mut().enumerate() {
+            if !child_process.has_exited() {
+                continue;
+            }
+            reaped = true;
+            match child_process.check(&self_clone, index as u8) {
+                Ok(_) => continue,
+                Err(Error::WaitError(e)) => {
+                    return Err(Error::WaitError(e));
+                }
+                Err(_e) => {
+                    continue;
+                }
+            }
+        }
+
+        if reaped {
+            self.reconcile_state();
+        }
+
+        Ok(())
+    }
+
     pub fn reconcile_state(&mut self) {
         let mut killed = false;
         let mut fatal = false;
@@ -71,6 +125,15 @@ impl Program {
         }
     }
 
+    // every process is running, the programs depending on this one can start
+    pub fn is_running(&self) -> bool {
+        !self.children.is_empty()
+            && self
+                .children
+                .iter()
+                .all(|c| c.state == ProgramState::Running)
+    }
+
     pub fn start_process(&mut self, origin: Origin) -> Result<()> {
         if origin == Origin::Config && !self.auto_start {
             return Ok(());
@@ -89,6 +152,12 @@ impl Program {
                         start_secs: Some(Instant::now()),
                         end_time: None,
                         restart_count: 1,
+                        next_retry: Some(Instant::now() + self.backoff.delay(1)),
+                        health: Health::default(),
+                        restart_requested: None,
+                        notify: Notify::default(),
+                        cgroup: None,
+                        group_signals: GroupSignals::default(),
                     })
                 }
             }
@@ -97,106 +166,136 @@ impl Program {
     }
 
     pub fn restart_processes(&mut self) -> Result<()> {
-        let stop_signal = self.stop_signal.clone() as u8;
+        let stop_signal = self.stop_signal.number();
         self.children
             .iter_mut()
             .try_for_each(|c| c.restart(stop_signal))?;
         Ok(())
     }
 
-    pub fn kill_processes(&mut self) {
-        self.children.iter_mut().for_each(|c| c.kill_program());
+    // send the stop signal to every child, then wait up to stoptime
+    // before killing the ones that are still alive
+    pub fn shutdown_processes(&mut self) {
+        let stop_signal = self.stop_signal.number();
+        stop_and_wait(&mut self.children, stop_signal, self.stop_time);
         self.children.clear();
+        self.remove_cgroup();
+    }
+
+    // true once every child asked to stop is gone
+    pub fn finish_stop(&mut self) -> bool {
+        finish_stop(&mut self.children, self.stop_time)
     }
 
     pub fn stop_processes(&mut self) -> Result<()> {
-        let stop_signal = self.stop_signal.clone() as u8;
+        let stop_signal = self.stop_signal.number();
         self.children
             .iter_mut()
             .try_for_each(|p| p.stop(stop_signal))
     }
 
-    pub fn update_program(&mut self, new_program: &mut Program) -> Result<()> {
-        // if any of these parameters change, we need to restart the program
-        if self.name != new_program.name
+    // send the stop signal to the children from `from`, and take them with
+    // the config they run with for `Programs::check` to finish stopping them
+    pub fn retire(&mut self, from: usize) -> Option<Program> {
+        if from >= self.children.len() {
+            return None;
+        }
+        let mut children = self.children.split_off(from);
+        let stop_signal = self.stop_signal.number();
+        for child_process in children.iter_mut() {
+            if let Err(e) = child_process.stop(stop_signal) {
+                let _ = log(format!("Failed to stop process: {}\n", e), LogInfo::Error);
+            }
+        }
+        Some(Program {
+            children,
+            ..self.clone()
+        })
+    }
+
+    // if any of these parameters change, we need to restart the program.
+    // The others are left out on purpose, the running processes follow the
+    // new config without a restart: numprocs adds or retires processes,
+    // startsecs, stoptime and backoff are only read when a process starts,
+    // stops or is retried, depends_on and priority only order the starts
+    // and stops, and the healthcheck is probed by the supervisor without
+    // the process taking part in it
+    pub fn needs_restart(&self, new_program: &Program) -> bool {
+        self.name != new_program.name
             || self.cmd != new_program.cmd
+            || self.shell != new_program.shell
             || self.auto_restart != new_program.auto_restart
             || self.exitcodes != new_program.exitcodes
             || self.start_retries != new_program.start_retries
             || self.auto_start != new_program.auto_start
             || self.stop_signal != new_program.stop_signal
+            || self.stop_as_group != new_program.stop_as_group
+            || self.kill_as_group != new_program.kill_as_group
             || self.env != new_program.env
             || self.working_dir != new_program.working_dir
             || self.umask != new_program.umask
+            || self.user != new_program.user
+            || self.group != new_program.group
+            || self.supplementary_groups != new_program.supplementary_groups
+            || self.limits != new_program.limits
+            || self.cgroup != new_program.cgroup
             || self.stdout != new_program.stdout
             || self.stderr != new_program.stderr
-        {
-            self.kill_processes();
-
-            new_program.start_process(Origin::Config)?;
-        // if the number of processes is less, we need to kill the extra processes
-        } else {
-            while self.children.len() > new_program.num_procs.into() {
-                if let Some(mut last) = self.children.pop() {
-                    last.kill_program();
-                }
-            }
-            new_program.children = self.children.drain(..).collect::<Vec<_>>();
-            if let Err(e) = new_program.start_process(Origin::Config) {
-                let _ = log(format!("Failed to start program: {}", e), LogInfo::Error);
-            }
+            || self.append != new_program.append
+            || self.rotation != new_program.rotation
+            || self.watchdog != new_program.watchdog
+            || self.notify != new_program.notify
+    }
+
+    // the processes keep running with the new config, the missing ones are
+    // started and the extra ones retired
+    pub fn update_program(&mut self, new_program: &mut Program) -> Option<Program> {
+        let extra = self.retire(new_program.num_procs.into());
+        new_program.children = std::mem::take(&mut self.children);
+        if let Err(e) = new_program.start_process(Origin::Config) {
+            let _ = log(format!("Failed to start program: {}", e), LogInfo::Error);
         }
-        Ok(())
+        extra
     }
 
-    pub fn status(&mut self) -> String {
-        format!(
-            "{} : {}",
-            self.name,
-            if let Some(s) = self.children.first().map(|c| c.state.clone()) {
-                s.to_string()
-            } else {
-                "Inactive program".to_string()
-            }
-        )
+    pub fn status(&mut self) -> ProgramStatus {
+        ProgramStatus {
+            name: self.name.clone(),
+            processes: self
+                .children
+                .iter()
+                .enumerate()
+                .map(|(index, c)| c.status(index))
+                .collect(),
+            limits: Some(self.limits.to_string()).filter(|limits| !limits.is_empty()),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::test_program;
 
     use crate::AutoRestart;
-    use crate::StopSignal;
+    use crate::Backoff;
+    use crate::Signal;
 
     use crate::ProgramState;
 
     #[test]
     fn test_check_inexistant_command() -> Result<()> {
         let mut program = Program {
-            name: "inexistent_command".to_string(),
-            cmd: ("/bin/toto".to_string(), vec!["3".to_string()]),
             num_procs: 3,
-
             auto_start: true,
             auto_restart: AutoRestart::Unexpected,
-
-            exitcodes: vec![0],
-
             start_retries: 3,
             start_secs: 1,
-
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+            stop_signal: Signal::USR1,
+            ..test_program("inexistent_command", "/bin/toto 3")
         };
 
-        let _ = program.start_process(Origin::Config)?;
+        program.start_process(Origin::Config)?;
         std::thread::sleep(std::time::Duration::from_secs(1));
 
         assert_eq!(
@@ -210,64 +309,121 @@ mod tests {
     #[test]
     fn test_check_autostart_false() -> Result<()> {
         let mut program = Program {
-            name: "inexistent_command".to_string(),
-            cmd: ("/bin/sleep".to_string(), vec!["3".to_string()]),
             num_procs: 3,
-
-            auto_start: false,
             auto_restart: AutoRestart::Unexpected,
+            start_retries: 3,
+            start_secs: 1,
+            stop_signal: Signal::USR1,
+            ..test_program("inexistent_command", "/bin/sleep 3")
+        };
 
-            exitcodes: vec![0],
+        program.start_process(Origin::Config)?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
 
+        assert_eq!(program.children.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_stop_signal() -> Result<()> {
+        let mut program = Program {
+            num_procs: 2,
+            auto_start: true,
+            auto_restart: AutoRestart::Unexpected,
             start_retries: 3,
             start_secs: 1,
+            stop_time: 5,
+            ..test_program("sleep_shutdown", "/bin/sleep 10")
+        };
 
-            stop_signal: StopSignal::Usr1,
-            stop_time: 1,
-            env: None,
-            working_dir: ".".to_string(),
-            umask: "0o022".to_string(),
-            stdout: "abc".to_string(),
-            stderr: "abc".to_string(),
-            children: vec![],
+        program.start_process(Origin::Config)?;
+        let start = Instant::now();
+        program.shutdown_processes();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(program.children.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shutdown_escalates_after_stoptime() -> Result<()> {
+        let mut program = Program {
+            auto_start: true,
+            auto_restart: AutoRestart::Unexpected,
+            start_retries: 3,
+            start_secs: 1,
+            ..test_program(
+                "ignore_term",
+                "/bin/sh -c 'trap '\\'''\\'' TERM; exec sleep 10'",
+            )
         };
 
-        let _ = program.start_process(Origin::Config)?;
-        std::thread::sleep(std::time::Duration::from_secs(1));
+        program.start_process(Origin::Config)?;
+        std::thread::sleep(Duration::from_millis(100));
+        let start = Instant::now();
+        program.shutdown_processes();
 
-        assert_eq!(program.children.len(), 0);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(2));
+        assert!(program.children.is_empty());
         Ok(())
     }
 
     #[test]
-    fn test_check_nginx_config() -> Result<()> {
+    fn test_status_every_process() -> Result<()> {
         let mut program = Program {
-            name: "nging".to_string(),
-            cmd: (
-                "/usr/local/bin/nginx".to_string(),
-                vec!["-c".to_string(), "/etc/nginx/test.conf".to_string()],
-            ),
-            num_procs: 1,
-            umask: "0o022".to_string(),
+            num_procs: 2,
             auto_start: true,
             auto_restart: AutoRestart::Unexpected,
+            start_retries: 3,
+            start_secs: 1,
+            ..test_program("sleep_status", "/bin/sleep 3")
+        };
 
-            exitcodes: vec![0, 2],
+        assert!(program.status().processes.is_empty());
+
+        program.start_process(Origin::Config)?;
+        let status = program.status();
+        assert_eq!(status.name, "sleep_status");
+        assert_eq!(status.processes.len(), 2);
+        for (index, (process, child)) in status
+            .processes
+            .iter()
+            .zip(program.children.iter())
+            .enumerate()
+        {
+            assert_eq!(process.index, index);
+            assert_eq!(process.state, ProgramState::Starting);
+            assert_eq!(process.pid, child.pid());
+            assert_eq!(process.uptime, Some(0));
+            assert_eq!(process.restarts, 0);
+        }
+        program.shutdown_processes();
+        Ok(())
+    }
 
+    #[test]
+    fn test_check_nginx_config() -> Result<()> {
+        let mut program = Program {
+            auto_start: true,
+            auto_restart: AutoRestart::Unexpected,
+            exitcodes: vec![0, 2],
             start_retries: 3,
             start_secs: 5,
-
-            stop_signal: StopSignal::Term,
             stop_time: 10,
-            env: None,
             working_dir: "/tmp".to_string(),
             stdout: "/tmp/nginx.stdout".to_string(),
             stderr: "/tmp/nginx.stderr".to_string(),
-            children: vec![],
+            // retry right away
+            backoff: Backoff {
+                base: 0,
+                ..Backoff::default()
+            },
+            ..test_program("nging", "/usr/local/bin/nginx -c /etc/nginx/test.conf")
         };
 
         // 1er
-        let _ = program.start_process(Origin::Config)?;
+        program.start_process(Origin::Config)?;
         std::thread::sleep(std::time::Duration::from_secs(1));
 
         assert_eq!(program.children.len(), 1);
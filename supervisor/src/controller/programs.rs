@@ -1,9 +1,10 @@
 use logger::{log, LogInfo};
 
-use crate::Action;
-use std::{collections::HashSet, fs::File, io::BufReader};
+use super::planner::{start_order, stop_steps};
+use crate::{Action, Payload, ProgramStatus};
+use std::{fs::File, io::BufReader};
 
-use crate::model::{Error, Origin, Programs, Result};
+use crate::model::{Error, Origin, Programs, Result, Signal};
 
 impl Programs {
     // loads a new configuration from a file, returns it. Doesn't change the current state
@@ -20,6 +21,15 @@ impl Programs {
                         new_config.programs.iter_mut().for_each(|(name, program)| {
                             program.name = name.clone();
                         });
+                        for program in new_config.programs.values() {
+                            program.check_log_paths()?;
+                            program.credentials()?;
+                            program.check_limits()?;
+                            program.check_cgroup()?;
+                        }
+                        new_config.check_groups()?;
+                        // reject unknown dependencies and cycles
+                        start_order(&new_config.programs)?;
                         if args.next().is_some() {
                             Err(Error::TooManyArguments)
                         } else {
@@ -35,45 +45,118 @@ impl Programs {
             None => Err(Error::NoFilenameProvided),
         }
     }
-    pub fn new(start_process: bool) -> Result<Programs> {
-        let path = match std::env::var("TASKMASTER_CONFIG_FILE_PATH") {
-            Ok(path) => path,
+    fn config_path() -> Result<String> {
+        match std::env::var("TASKMASTER_CONFIG_FILE_PATH") {
+            Ok(path) => Ok(path),
             Err(e) => {
                 log(
                     "Could not find env variable for taskmaster config\n".to_string(),
                     LogInfo::Error,
                 )?;
-                return Err(Error::ConfigEnvVarNotFound(e));
+                Err(Error::ConfigEnvVarNotFound(e))
             }
-        };
-        Self::new_from_path(path, start_process)
+        }
+    }
+
+    pub fn new(start_process: bool) -> Result<Programs> {
+        Self::new_from_path(Self::config_path()?, start_process)
     }
 
     pub fn check(&mut self) -> Result<()> {
-        self.programs.iter_mut().try_for_each(|(_, p)| p.check())
+        self.programs.iter_mut().try_for_each(|(_, p)| p.check())?;
+        self.finish_stopping();
+        self.start_pending();
+        Ok(())
     }
 
-    pub fn update_config_with_config(&mut self, mut new_config: Self) -> Result<Programs> {
-        let mut dealt = HashSet::new();
-
-        for (name, new_p) in new_config.programs.iter_mut() {
-            dealt.insert(name);
-            if let Some(p) = self.programs.get_mut(name) {
-                p.update_program(new_p)?;
-            } else {
-                new_p.start_process(Origin::Config)?;
+    // the processes stopped by a reload are killed if still alive after
+    // the stoptime of their program
+    fn finish_stopping(&mut self) {
+        self.stopping.retain_mut(|p| {
+            if !p.finish_stop() {
+                return true;
+            }
+            p.remove_cgroup();
+            false
+        });
+    }
+
+    // queue the programs in start order, they are started as soon as
+    // every program they depend on is running
+    fn queue_start(&mut self, names: &[String], origin: Origin) -> Result<()> {
+        for name in start_order(&self.programs)? {
+            if names.contains(&name) && !self.pending.iter().any(|(n, _)| *n == name) {
+                self.pending.push((name, origin.clone()));
+            }
+        }
+        self.start_pending();
+        Ok(())
+    }
+
+    // start the queued programs whose dependencies are running, and
+    // whose processes from before a reload are gone
+    fn start_pending(&mut self) {
+        for (name, origin) in std::mem::take(&mut self.pending) {
+            let Some(program) = self.programs.get(&name) else {
+                continue;
+            };
+            let ready = program
+                .depends_on
+                .iter()
+                .all(|d| self.programs.get(d).is_some_and(|p| p.is_running()))
+                && !self.stopping.iter().any(|p| p.name == name);
+            if !ready {
+                self.pending.push((name, origin));
+            } else if let Some(program) = self.programs.get_mut(&name) {
+                if let Err(e) = program.start_process(origin) {
+                    let _ = log(format!("Failed to start {name}: {e}\n"), LogInfo::Error);
+                }
             }
         }
+    }
+
+    pub fn reap_children(&mut self) -> Result<()> {
         self.programs
             .iter_mut()
-            .filter(|(name, _)| !dealt.contains(name))
-            .for_each(|(name, p)| {
-                let _ = log(
-                    format!("Removing {name} from programs since it's not in the config anymore\n"),
-                    LogInfo::Info,
-                );
-                p.kill_processes()
-            });
+            .try_for_each(|(_, p)| p.reap_children())
+    }
+
+    // the programs are updated in start order and the removed ones are
+    // stopped in shutdown order. Nothing waits for the processes to stop,
+    // the restarted programs are started by `check` once they are gone
+    pub fn update_config_with_config(&mut self, mut new_config: Self) -> Result<Programs> {
+        let mut added = vec![];
+        let mut stopping = std::mem::take(&mut self.stopping);
+
+        for name in start_order(&new_config.programs)? {
+            let new_p = new_config.programs.get_mut(&name).unwrap();
+            match self.programs.get_mut(&name) {
+                Some(p) if p.needs_restart(new_p) => {
+                    stopping.extend(p.retire(0));
+                    added.push(name);
+                }
+                Some(p) => stopping.extend(p.update_program(new_p)),
+                None => added.push(name),
+            }
+        }
+        let removed = stop_steps(&self.programs)
+            .unwrap_or_else(|_| vec![self.programs.keys().cloned().collect()])
+            .into_iter()
+            .flatten()
+            .filter(|name| !new_config.programs.contains_key(name));
+        for name in removed {
+            let _ = log(
+                format!("Removing {name} from programs since it's not in the config anymore\n"),
+                LogInfo::Info,
+            );
+            if let Some(p) = self.programs.get_mut(&name) {
+                stopping.extend(p.retire(0));
+            }
+        }
+        new_config.stopping = stopping;
+        // the new programs wait for their dependencies like the ones still queued
+        new_config.pending = std::mem::take(&mut self.pending);
+        new_config.queue_start(&added, Origin::Config)?;
         Ok(new_config)
     }
 
@@ -82,24 +165,123 @@ impl Programs {
         self.update_config_with_config(new_config)
     }
 
+    // apply the config file at `path` in place, the queued and stopping
+    // programs are kept with it
+    pub fn reload(&mut self, path: String) -> Result<()> {
+        let new_config = Self::new_from_path(path, false)?;
+        *self = self.update_config_with_config(new_config)?;
+        Ok(())
+    }
+
+    // gracefully stop the programs before the ones they depend on, the
+    // programs of a same step are stopped at once, each one is killed
+    // if still alive after its own stoptime
+    pub fn shutdown(&mut self) {
+        self.pending.clear();
+        // a reload already sent them their stop signal
+        self.finish_stopping();
+        while !self.stopping.is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            self.finish_stopping();
+        }
+        // the config was checked when loaded, stop everything at once otherwise
+        let steps = stop_steps(&self.programs)
+            .unwrap_or_else(|_| vec![self.programs.keys().cloned().collect()]);
+        for step in steps {
+            let mut programs = self
+                .programs
+                .iter_mut()
+                .filter(|(name, _)| step.contains(name))
+                .collect::<Vec<_>>();
+            programs.iter_mut().for_each(|(name, p)| {
+                if let Err(e) = p.stop_processes() {
+                    let _ = log(format!("Failed to stop {name}: {e}\n"), LogInfo::Error);
+                }
+            });
+            loop {
+                let mut done = true;
+                for (_, p) in programs.iter_mut() {
+                    done &= p.finish_stop();
+                }
+                if done {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+        }
+        self.programs.iter_mut().for_each(|(_, p)| {
+            p.children.clear();
+            p.remove_cgroup();
+        });
+    }
+
+    // programs with dependencies wait for them to be running
     pub fn start_all(&mut self) -> Result<()> {
-        self.programs
+        let names = self.programs.keys().cloned().collect::<Vec<_>>();
+        self.queue_start(&names, Origin::Config)
+    }
+
+    // sorted by program name
+    pub fn status(&mut self) -> Vec<ProgramStatus> {
+        let mut status = self
+            .programs
             .iter_mut()
-            .try_for_each(|(_, p)| p.start_process(Origin::Config))
+            .map(|(_, p)| p.status())
+            .collect::<Vec<_>>();
+        status.sort_by(|a, b| a.name.cmp(&b.name));
+        status
     }
 
-    pub fn status(&mut self) -> String {
-        format!(
-            "{}\n",
-            self.programs
-                .iter_mut()
-                .map(|(_, p)| p.status())
-                .collect::<Vec<_>>()
-                .join(" // ")
-        )
+    // every group member must be a program of the config
+    fn check_groups(&self) -> Result<()> {
+        for (group, members) in self.groups.iter() {
+            if let Some(member) = members.iter().find(|m| !self.programs.contains_key(*m)) {
+                return Err(Error::De(format!(
+                    "Group {group} contains unknown program {member}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // turn the targets of a command into program names, a target is either a
+    // program name, `group:*` for every program of the group or `group:member`.
+    // Refuse the whole command if one of the targets is unknown
+    fn resolve(&self, targets: &[String]) -> Result<Vec<String>> {
+        let mut names: Vec<String> = vec![];
+        let mut unknown = vec![];
+        for target in targets {
+            let resolved = match target.split_once(':') {
+                Some((group, member)) => match self.groups.get(group) {
+                    Some(members) if member == "*" => Some(members.clone()),
+                    Some(members) if members.iter().any(|m| m == member) => {
+                        Some(vec![member.to_string()])
+                    }
+                    _ => None,
+                },
+                None if self.programs.contains_key(target) => Some(vec![target.clone()]),
+                None => None,
+            };
+            let Some(resolved) = resolved else {
+                unknown.push(target.clone());
+                continue;
+            };
+            for name in resolved {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        if unknown.is_empty() {
+            Ok(names)
+        } else {
+            Err(Error::UnknownPrograms(unknown))
+        }
     }
 
     pub fn stop(&mut self, programs: &[String]) -> Result<()> {
+        let programs = self.resolve(programs)?;
+        self.pending.retain(|(name, _)| !programs.contains(name));
         self.programs
             .iter_mut()
             .filter(|(name, _)| programs.contains(name))
@@ -108,14 +290,12 @@ impl Programs {
     }
 
     pub fn start(&mut self, programs: &[String]) -> Result<()> {
-        self.programs
-            .iter_mut()
-            .filter(|(name, _)| programs.contains(name))
-            .try_for_each(|(_, p)| p.start_process(Origin::CLI))?;
-        Ok(())
+        let programs = self.resolve(programs)?;
+        self.queue_start(&programs, Origin::CLI)
     }
 
     pub fn restart(&mut self, programs: &[String]) -> Result<()> {
+        let programs = self.resolve(programs)?;
         self.programs
             .iter_mut()
             .filter(|(name, _)| programs.contains(name))
@@ -125,30 +305,53 @@ impl Programs {
         Ok(())
     }
 
-    pub fn handle_action(&mut self, action: Action) -> Result<String> {
+    // the number of processes the signal was sent to
+    pub fn signal(&mut self, signal: Signal, programs: &[String]) -> Result<usize> {
+        let programs = self.resolve(programs)?;
+        Ok(self
+            .programs
+            .iter()
+            .filter(|(name, _)| programs.contains(name))
+            .flat_map(|(_, p)| &p.children)
+            .filter(|c| c.signal(signal))
+            .count())
+    }
+
+    pub fn handle_action(&mut self, action: Action) -> Result<Payload> {
         Ok(match action {
             Action::Start(programs) => {
                 self.start(&programs)?;
-                "Programs started\n".to_string()
+                Payload::Message("Programs started".to_string())
             }
             Action::Stop(programs) => {
                 self.stop(&programs)?;
-                "Programs stopped\n".to_string()
+                Payload::Message("Programs stopped".to_string())
             }
             Action::Restart(programs) => {
                 self.restart(&programs)?;
-                "Programs restarted\n".to_string()
+                Payload::Message("Programs restarted".to_string())
                 // self.relaunch(),
             }
-            Action::Status => self.status(),
+            Action::Signal(signal, programs) => {
+                let count = self.signal(signal, &programs)?;
+                Payload::Message(format!("Signal {signal} sent to {count} process(es)"))
+            }
+            Action::Status => Payload::Status(self.status()),
+            // following is handled in the server, which keeps the client
+            Action::Tail {
+                target,
+                output,
+                lines,
+                ..
+            } => Payload::Output(self.tail(&target, output, lines)?.0),
             // reload the config file
             Action::Reload => {
-                self.programs = self.update_config()?.programs;
-                "Reload done\n".to_string()
+                self.reload(Self::config_path()?)?;
+                Payload::Message("Reload done".to_string())
             }
             // clean stop the job control and exit
-            // Handled in the server
-            Action::Quit => {
+            // Handled in the server, as the authentication
+            Action::Quit | Action::Hello | Action::Auth(_) => {
                 unreachable!();
             }
         })
@@ -159,6 +362,7 @@ impl Programs {
 mod tests {
     use std::time::Duration;
 
+    use crate::model::{Cmd, Program, Watchdog};
     use crate::ProgramState;
 
     use super::*;
@@ -181,7 +385,7 @@ mod tests {
                 - 0
                 - 2
               startretries: 3
-              startsecs: 5
+              startsecs: 0
               stopsignal: TERM
               stoptime: 1
               stdout: "/tmp/nginx.stdout"
@@ -275,6 +479,308 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn reload_conf_does_not_wait_for_stop() -> Result<()> {
+        // ignores its stop signal, only killed after the stoptime
+        let ignore_term = |programs: &mut Programs| {
+            let sleep = programs.programs.get_mut("sleep").unwrap();
+            sleep.cmd = Cmd::from((
+                "/bin/sh".to_string(),
+                vec!["-c".to_string(), "trap '' TERM; exec sleep 10".to_string()],
+            ));
+        };
+        let mut programs = config();
+        ignore_term(&mut programs);
+        programs.start_all()?;
+        programs.check()?;
+        let id = first_child_pid(&programs);
+        let mut new_config = config();
+        ignore_term(&mut new_config);
+        new_config.programs.get_mut("sleep").unwrap().exitcodes = vec![3];
+
+        let start = std::time::Instant::now();
+        programs = programs.update_config_with_config(new_config)?;
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(programs.stopping.len(), 1);
+        assert!(first_child_pid(&programs).is_empty());
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(3);
+        while !programs.stopping.is_empty() && std::time::Instant::now() < deadline {
+            programs.check()?;
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert!(programs.stopping.is_empty());
+        let new_id = first_child_pid(&programs);
+        assert_eq!(new_id.len(), 1);
+        assert!(id != new_id);
+        programs.shutdown();
+        Ok(())
+    }
+    // the running process is replaced by one started with a NOTIFY_SOCKET
+    fn reload_gives_notify_socket(change: fn(&mut Program)) -> Result<()> {
+        let mut programs = config();
+        programs.start_all()?;
+        programs.check()?;
+        let id = first_child_pid(&programs);
+        let mut new_config = config();
+        change(new_config.programs.get_mut("sleep").unwrap());
+        programs = programs.update_config_with_config(new_config)?;
+        assert_eq!(programs.stopping.len(), 1);
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(3);
+        while first_child_pid(&programs).is_empty() && std::time::Instant::now() < deadline {
+            programs.check()?;
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert!(id != first_child_pid(&programs));
+        assert!(programs.programs["sleep"].children[0]
+            .notify
+            .socket
+            .is_some());
+        programs.shutdown();
+        Ok(())
+    }
+    #[test]
+    fn reload_conf_turn_on_watchdog() -> Result<()> {
+        reload_gives_notify_socket(|sleep| {
+            sleep.watchdog = Some(Watchdog {
+                timeout: 5,
+                file: None,
+            })
+        })
+    }
+    #[test]
+    fn reload_conf_turn_on_notify() -> Result<()> {
+        reload_gives_notify_socket(|sleep| sleep.notify = true)
+    }
+    #[test]
+    fn reap_children_on_exit() -> Result<()> {
+        let mut programs = config();
+        programs.start_all()?;
+        programs.check()?;
+        assert_eq!(first_child_state(&programs), ProgramState::Running);
+        let pid = first_child_pid(&programs)[0];
+        let _ = unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) };
+        std::thread::sleep(Duration::from_millis(100));
+        programs.reap_children()?;
+        assert_eq!(first_child_state(&programs), ProgramState::Backoff);
+        let child = programs
+            .programs
+            .get("sleep")
+            .unwrap()
+            .children
+            .first()
+            .unwrap();
+        assert!(child.next_retry.is_some());
+        Ok(())
+    }
+    #[test]
+    fn signal_running_processes() -> Result<()> {
+        let mut programs = config();
+        programs.start_all()?;
+        programs.check()?;
+        let action = Action::Signal(Signal::KILL, vec!["sleep".to_string()]);
+        assert_eq!(
+            programs.handle_action(action)?,
+            Payload::Message("Signal KILL sent to 1 process(es)".to_string())
+        );
+        std::thread::sleep(Duration::from_millis(100));
+        programs.reap_children()?;
+        assert_eq!(first_child_state(&programs), ProgramState::Backoff);
+        // it isn't running anymore
+        assert_eq!(programs.signal(Signal::TERM, &["sleep".to_string()])?, 0);
+        assert!(matches!(
+            programs.signal(Signal::TERM, &["nope".to_string()]),
+            Err(Error::UnknownPrograms(_))
+        ));
+        Ok(())
+    }
+    fn config_with_groups() -> Programs {
+        let mut programs = config();
+        let mut other = programs.programs.get("sleep").unwrap().clone();
+        other.name = "other".to_string();
+        programs.programs.insert("other".to_string(), other);
+        programs.groups.insert(
+            "tier".to_string(),
+            vec!["sleep".to_string(), "other".to_string()],
+        );
+        programs.groups.insert("empty".to_string(), vec![]);
+        programs
+    }
+    #[test]
+    fn resolve_targets() -> Result<()> {
+        let programs = config_with_groups();
+        let targets = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(programs.resolve(&targets(&["sleep"]))?, vec!["sleep"]);
+        assert_eq!(
+            programs.resolve(&targets(&["tier:*"]))?,
+            vec!["sleep", "other"]
+        );
+        assert_eq!(programs.resolve(&targets(&["tier:other"]))?, vec!["other"]);
+        // no duplicates
+        assert_eq!(
+            programs.resolve(&targets(&["other", "tier:*"]))?,
+            vec!["other", "sleep"]
+        );
+        assert!(programs.resolve(&targets(&["empty:*"]))?.is_empty());
+        match programs.resolve(&targets(&["tier:nope", "nope:*", "sleep", "tier"])) {
+            Err(Error::UnknownPrograms(unknown)) => {
+                assert_eq!(unknown, vec!["tier:nope", "nope:*", "tier"])
+            }
+            _ => panic!("expected an unknown program error"),
+        }
+        Ok(())
+    }
+    #[test]
+    fn groups_with_unknown_program() {
+        let mut programs = config_with_groups();
+        assert!(programs.check_groups().is_ok());
+        programs
+            .groups
+            .insert("broken".to_string(), vec!["nope".to_string()]);
+        assert!(matches!(programs.check_groups(), Err(Error::De(_))));
+    }
+    #[test]
+    fn stop_group() -> Result<()> {
+        let mut programs = config_with_groups();
+        programs.start_all()?;
+        programs.handle_action(Action::Stop(vec!["tier:*".to_string()]))?;
+        assert!(programs
+            .programs
+            .values()
+            .all(|p| p.children.iter().all(|c| c.state == ProgramState::Stopping)));
+        programs.shutdown();
+        Ok(())
+    }
+    #[test]
+    fn start_after_dependencies() -> Result<()> {
+        let mut programs = config();
+        let mut web = programs.programs.get("sleep").unwrap().clone();
+        web.name = "web".to_string();
+        web.depends_on = vec!["sleep".to_string()];
+        programs.programs.insert("web".to_string(), web);
+
+        programs.start_all()?;
+        assert_eq!(first_child_state(&programs), ProgramState::Starting);
+        assert!(programs.programs["web"].children.is_empty());
+        assert_eq!(programs.pending.len(), 1);
+
+        // sleep is running after this check, web starts right after it
+        programs.check()?;
+        assert_eq!(first_child_state(&programs), ProgramState::Running);
+        assert_eq!(programs.programs["web"].children.len(), 1);
+        assert!(programs.pending.is_empty());
+        programs.shutdown();
+        Ok(())
+    }
+    #[test]
+    fn reload_with_dependent_program() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("taskmaster-reload-{}.yml", std::process::id()));
+        let data = r#"
+        programs:
+            db:
+              cmd: "/usr/bin/sleep 2"
+              numprocs: 1
+              workingdir: /tmp
+              autostart: true
+              autorestart: never
+              exitcodes: [0]
+              startretries: 0
+              startsecs: 0
+              stopsignal: TERM
+              stoptime: 1
+              stdout: /dev/null
+              stderr: /dev/null
+            web:
+              cmd: "/usr/bin/sleep 2"
+              numprocs: 1
+              workingdir: /tmp
+              autostart: true
+              autorestart: never
+              exitcodes: [0]
+              startretries: 0
+              startsecs: 0
+              stopsignal: TERM
+              stoptime: 1
+              stdout: /dev/null
+              stderr: /dev/null
+              depends_on: [db]
+        "#;
+        std::fs::write(&path, data).unwrap();
+        let mut programs = config();
+        programs.start_all()?;
+        let reload = programs.reload(path.display().to_string());
+        std::fs::remove_file(&path).unwrap();
+        reload?;
+
+        // web waits for db to be running
+        assert!(programs.programs["web"].children.is_empty());
+        assert_eq!(programs.pending.len(), 1);
+        programs.check()?;
+        assert_eq!(programs.programs["web"].children.len(), 1);
+        assert!(programs.pending.is_empty());
+        programs.shutdown();
+        Ok(())
+    }
+    #[test]
+    fn dependency_cycle_in_config() {
+        let path =
+            std::env::temp_dir().join(format!("taskmaster-cycle-{}.yml", std::process::id()));
+        let data = r#"
+        programs:
+            a:
+              cmd: "/bin/true"
+              numprocs: 1
+              workingdir: /tmp
+              autostart: false
+              autorestart: never
+              exitcodes: [0]
+              startretries: 0
+              startsecs: 0
+              stopsignal: TERM
+              stoptime: 1
+              stdout: /dev/null
+              stderr: /dev/null
+              depends_on: [b]
+            b:
+              cmd: "/bin/true"
+              numprocs: 1
+              workingdir: /tmp
+              autostart: false
+              autorestart: never
+              exitcodes: [0]
+              startretries: 0
+              startsecs: 0
+              stopsignal: TERM
+              stoptime: 1
+              stdout: /dev/null
+              stderr: /dev/null
+              depends_on: [a]
+        "#;
+        std::fs::write(&path, data).unwrap();
+        let result = Programs::new_from_path(path.display().to_string(), false);
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(Error::DependencyCycle(cycle)) => assert_eq!(cycle, vec!["a", "b", "a"]),
+            _ => panic!("expected a dependency cycle"),
+        }
+    }
+    #[test]
+    fn unknown_programs() -> Result<()> {
+        let mut programs = config();
+        programs.start_all()?;
+        let names = vec!["sleep".to_string(), "nope".to_string()];
+        match programs.handle_action(Action::Stop(names)) {
+            Err(Error::UnknownPrograms(unknown)) => assert_eq!(unknown, vec!["nope"]),
+            _ => panic!("expected an unknown program error"),
+        }
+        // nothing was stopped
+        assert_eq!(first_child_state(&programs), ProgramState::Starting);
+        programs.shutdown();
+        Ok(())
+    }
+    #[test]
     fn start_program_01() -> Result<()> {
         let mut programs = config();
         programs.start_all()?;
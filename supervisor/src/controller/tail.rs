@@ -0,0 +1,166 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::model::{Error, Follow, Output, Programs, Result};
+
+// size of the blocks read from the end of a file
+const TAIL_BLOCK: u64 = 8192;
+// most bytes sent to a follower at once
+const FOLLOW_CHUNK_MAX: u64 = 64 * 1024;
+
+// last `lines` lines of a file and the size it had when read
+fn read_last_lines(path: &Path, lines: usize) -> io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if lines == 0 {
+        return Ok((String::new(), size));
+    }
+
+    // a newline ending the file doesn't start another line
+    let enough = |buf: &[u8]| {
+        let content = buf.strip_suffix(b"\n").unwrap_or(buf);
+        content.iter().filter(|&&b| b == b'\n').count() >= lines
+    };
+    let mut start = size;
+    let mut buf = vec![];
+    while start > 0 && !enough(&buf) {
+        let len = start.min(TAIL_BLOCK);
+        start -= len;
+        let mut block = vec![0; len as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        block.extend(buf);
+        buf = block;
+    }
+
+    let content = buf.strip_suffix(b"\n").unwrap_or(&buf);
+    let mut begin = 0;
+    let mut count = 0;
+    for (index, &b) in content.iter().enumerate().rev() {
+        if b == b'\n' {
+            count += 1;
+            if count == lines {
+                begin = index + 1;
+                break;
+            }
+        }
+    }
+    Ok((String::from_utf8_lossy(&buf[begin..]).into_owned(), size))
+}
+
+impl Follow {
+    // what was written since the last call, from the start again when the
+    // file was truncated or rotated. Nothing while a rotated file is missing
+    pub fn read_new(&mut self) -> io::Result<String> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(String::new()),
+            Err(e) => return Err(e),
+        };
+        let size = file.metadata()?.len();
+        if size < self.offset {
+            self.offset = 0;
+        }
+        let len = (size - self.offset).min(FOLLOW_CHUNK_MAX);
+        let mut buf = vec![0; len as usize];
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.read_exact(&mut buf)?;
+        self.offset += len;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+impl Programs {
+    // file written with the output of a process, `target` is
+    // `program` for its first process or `program:index`
+    fn output_path(&self, target: &str, output: Output) -> Result<PathBuf> {
+        let unknown = || Error::UnknownPrograms(vec![target.to_string()]);
+        let (name, index) = match target.rsplit_once(':') {
+            Some((name, index)) => (name, index.parse::<u8>().map_err(|_| unknown())?),
+            None => (target, 0),
+        };
+        let program = self
+            .programs
+            .get(name)
+            .filter(|program| index < program.num_procs)
+            .ok_or_else(unknown)?;
+
+        let expanded = program.expanded(index)?;
+        let path = match output {
+            Output::Stdout => expanded.stdout,
+            Output::Stderr => expanded.stderr,
+        };
+        if path.starts_with("/dev/") {
+            return Err(Error::Read(format!(
+                "The {output} of {target} is not written to a file"
+            )));
+        }
+        Ok(PathBuf::from(path))
+    }
+
+    // last lines of the output of a process, and where to follow it from
+    pub fn tail(&self, target: &str, output: Output, lines: usize) -> Result<(String, Follow)> {
+        let path = self.output_path(target, output)?;
+        let (text, offset) = read_last_lines(&path, lines)
+            .map_err(|e| Error::Read(format!("{} : {e}", path.display())))?;
+        Ok((text, Follow { path, offset }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("taskmaster-{name}-{}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_last_lines() -> io::Result<()> {
+        let path = temp_file("tail", "one\ntwo\nthree\n");
+        assert_eq!(read_last_lines(&path, 2)?, ("two\nthree\n".to_string(), 14));
+        assert_eq!(read_last_lines(&path, 3)?.0, "one\ntwo\nthree\n");
+        assert_eq!(read_last_lines(&path, 10)?.0, "one\ntwo\nthree\n");
+        assert_eq!(read_last_lines(&path, 0)?.0, "");
+
+        // the last line doesn't need a newline
+        fs::write(&path, "one\ntwo")?;
+        assert_eq!(read_last_lines(&path, 1)?.0, "two");
+
+        // lines spread over several blocks
+        let long = "x".repeat(TAIL_BLOCK as usize);
+        fs::write(&path, format!("first\n{long}\n{long}\nlast\n"))?;
+        assert_eq!(read_last_lines(&path, 2)?.0, format!("{long}\nlast\n"));
+        assert_eq!(read_last_lines(&path, 4)?.0.lines().next(), Some("first"));
+        fs::remove_file(&path)
+    }
+
+    #[test]
+    fn test_follow() -> io::Result<()> {
+        let path = temp_file("follow", "old\n");
+        let mut follow = Follow {
+            path: path.clone(),
+            offset: 4,
+        };
+        assert_eq!(follow.read_new()?, "");
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"new\n")?;
+        assert_eq!(follow.read_new()?, "new\n");
+        assert_eq!(follow.read_new()?, "");
+
+        // rotated
+        fs::remove_file(&path)?;
+        assert_eq!(follow.read_new()?, "");
+        fs::write(&path, "again\n")?;
+        assert_eq!(follow.read_new()?, "again\n");
+        fs::remove_file(&path)
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, SystemTime};
+
+use logger::{log, LogInfo};
+
+use crate::model::{ChildProcess, Program, Result};
+
+impl ChildProcess {
+    // time since the last heartbeat, or since the process started
+    // when it didn't give any yet
+    fn since_heartbeat(&self, file: Option<&str>) -> Duration {
+        let since_start = self
+            .start_secs
+            .map_or(Duration::ZERO, |start_time| start_time.elapsed());
+        let since_heartbeat = match file {
+            Some(file) => std::fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok()),
+            None => self.notify.heartbeat.map(|heartbeat| heartbeat.elapsed()),
+        };
+        since_heartbeat.map_or(since_start, |since| since.min(since_start))
+    }
+
+    // called while the process is running, asks for a restart when it
+    // gave no heartbeat for the watchdog timeout
+    pub fn check_watchdog(&mut self, config: &Program, process_number: u8) -> Result<()> {
+        let Some(watchdog) = &config.watchdog else {
+            return Ok(());
+        };
+        let since_heartbeat = self.since_heartbeat(watchdog.file.as_deref());
+        if since_heartbeat < Duration::from_secs(watchdog.timeout.into()) {
+            return Ok(());
+        }
+        let _ = log(
+            format!(
+                "{}--{}: No heartbeat for {}s, restarting the process\n",
+                config.name,
+                process_number,
+                since_heartbeat.as_secs()
+            ),
+            LogInfo::Warn,
+        );
+        self.request_restart(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{test_program, AutoRestart, ProgramState, Watchdog};
+    use std::os::unix::net::UnixDatagram;
+    use std::thread::sleep;
+
+    fn program(file: Option<String>) -> Program {
+        Program {
+            auto_restart: AutoRestart::Always,
+            start_retries: 3,
+            watchdog: Some(Watchdog { timeout: 1, file }),
+            ..test_program("sleep_watchdog", "/bin/sleep 10")
+        }
+    }
+
+    #[test]
+    fn test_watchdog_file() -> Result<()> {
+        let file =
+            std::env::temp_dir().join(format!("taskmaster-{}.heartbeat", std::process::id()));
+        let program = program(Some(file.display().to_string()));
+        let mut child_process = ChildProcess::start(&program, 0)?;
+        assert!(child_process.notify.socket.is_none());
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
+
+        for _ in 0..3 {
+            std::fs::write(&file, "")?;
+            sleep(Duration::from_millis(500));
+            child_process.check(&program, 0)?;
+            assert!(child_process.restart_requested.is_none());
+        }
+
+        sleep(Duration::from_millis(1100));
+        child_process.check(&program, 0)?;
+        assert!(child_process.restart_requested.is_some());
+        sleep(Duration::from_millis(100));
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Backoff);
+
+        std::fs::remove_file(&file)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchdog_notify() -> Result<()> {
+        let program = program(None);
+        let mut child_process = ChildProcess::start(&program, 0)?;
+        let path = child_process.notify.socket.as_ref().unwrap().path.clone();
+        child_process.check(&program, 0)?;
+        assert_eq!(child_process.state, ProgramState::Running);
+
+        let client = UnixDatagram::unbound()?;
+        for _ in 0..3 {
+            client.send_to(b"WATCHDOG=1", &path)?;
+            sleep(Duration::from_millis(500));
+            child_process.check(&program, 0)?;
+            assert!(child_process.notify.heartbeat.is_some());
+            assert!(child_process.restart_requested.is_none());
+        }
+
+        sleep(Duration::from_millis(1100));
+        child_process.check(&program, 0)?;
+        assert!(child_process.restart_requested.is_some());
+        child_process.kill_program();
+        Ok(())
+    }
+}
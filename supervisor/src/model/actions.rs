@@ -1,10 +1,17 @@
 use std::fmt::Display;
 
+use crate::model::{Output, Signal};
+
+// lines printed by `tail` without `-n`
+const DEFAULT_TAIL_LINES: usize = 10;
+
 #[derive(Debug)]
 pub enum ParseActionError {
     NoCommandFound,
     NoProgramsProvided(String),
     ToManyArguments(String),
+    WrongArgumentCount(String, usize),
+    InvalidArgument(String, String),
     UnrecognizedAction(String),
 }
 
@@ -22,6 +29,20 @@ impl Display for ParseActionError {
                     a
                 )
             }
+            ParseActionError::WrongArgumentCount(a, n) => {
+                writeln!(
+                    f,
+                    "\x1B[31mThe command {:?} needs exactly {} argument(s)\x1B[0m",
+                    a, n
+                )
+            }
+            ParseActionError::InvalidArgument(a, arg) => {
+                writeln!(
+                    f,
+                    "\x1B[31mThe command {:?} doesn't accept the argument {:?}\x1B[0m",
+                    a, arg
+                )
+            }
             ParseActionError::UnrecognizedAction(a) => {
                 writeln!(f, "\x1B[31mThe command {:?} is not recognized\x1B[0m", a)
             }
@@ -31,38 +52,124 @@ impl Display for ParseActionError {
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum Action {
+    // first message of a client, answered without authentication
+    Hello,
+    // authenticate the client with the shared token
+    Auth(String),
     Quit,
     Reload,
     Restart(Vec<String>),
     Status,
     Start(Vec<String>),
     Stop(Vec<String>),
+    // send a signal to the running processes of the programs
+    Signal(Signal, Vec<String>),
+    // last lines of the output of a process, `target` is `program[:index]`
+    Tail {
+        target: String,
+        output: Output,
+        lines: usize,
+        follow: bool,
+    },
 }
 
-impl ToString for Action {
-    fn to_string(&self) -> String {
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            Action::Quit => String::from("quit"),
-            Action::Reload => String::from("reload"),
-            Action::Restart(programs) => format!("restart {}", programs.join(" ")),
-            Action::Status => String::from("status"),
-            Action::Start(programs) => format!("start {}", programs.join(" ")),
-            Action::Stop(programs) => format!("stop {}", programs.join(" ")),
+            Action::Hello => write!(f, "hello"),
+            Action::Auth(token) => write!(f, "auth {}", token),
+            Action::Quit => write!(f, "quit"),
+            Action::Reload => write!(f, "reload"),
+            Action::Restart(programs) => write!(f, "restart {}", programs.join(" ")),
+            Action::Status => write!(f, "status"),
+            Action::Start(programs) => write!(f, "start {}", programs.join(" ")),
+            Action::Stop(programs) => write!(f, "stop {}", programs.join(" ")),
+            Action::Signal(signal, programs) => {
+                write!(f, "signal {} {}", signal, programs.join(" "))
+            }
+            Action::Tail {
+                target,
+                output,
+                lines,
+                follow,
+            } => {
+                write!(f, "tail ")?;
+                if *follow {
+                    write!(f, "-f ")?;
+                }
+                write!(f, "-n {} {} {}", lines, target, output)
+            }
         }
     }
 }
 
-impl TryFrom<String> for Action {
-    type Error = ParseActionError;
+impl Action {
+    // name of the action, as typed in the shell
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Hello => "hello",
+            Action::Auth(_) => "auth",
+            Action::Quit => "quit",
+            Action::Reload => "reload",
+            Action::Restart(_) => "restart",
+            Action::Status => "status",
+            Action::Start(_) => "start",
+            Action::Stop(_) => "stop",
+            Action::Signal(_, _) => "signal",
+            Action::Tail { .. } => "tail",
+        }
+    }
 
-    fn try_from(cmd: String) -> Result<Self, Self::Error> {
-        let mut args = cmd.split_whitespace();
-        let Some(action) = args.next() else {
+    // arguments of the action, each one kept whole even with whitespaces
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Action::Hello | Action::Quit | Action::Reload | Action::Status => vec![],
+            Action::Auth(token) => vec![token.clone()],
+            Action::Restart(programs) | Action::Start(programs) | Action::Stop(programs) => {
+                programs.clone()
+            }
+            Action::Signal(signal, programs) => std::iter::once(signal.to_string())
+                .chain(programs.iter().cloned())
+                .collect(),
+            Action::Tail {
+                target,
+                output,
+                lines,
+                follow,
+            } => {
+                let mut args = vec![];
+                if *follow {
+                    args.push("-f".to_string());
+                }
+                args.extend([
+                    "-n".to_string(),
+                    lines.to_string(),
+                    target.clone(),
+                    output.to_string(),
+                ]);
+                args
+            }
+        }
+    }
+
+    // build the action from its name and its arguments
+    pub fn parse(action: &str, programs: Vec<String>) -> Result<Self, ParseActionError> {
+        if action.is_empty() {
             return Err(ParseActionError::NoCommandFound);
-        };
-        let programs = args.map(|e| e.to_string()).collect::<Vec<String>>();
+        }
         let lower_action = action.to_lowercase();
         match lower_action.as_str() {
+            "hello" => {
+                if programs.is_empty() {
+                    Ok(Action::Hello)
+                } else {
+                    Err(ParseActionError::ToManyArguments(lower_action))
+                }
+            }
+            "auth" => match programs.as_slice() {
+                [token] => Ok(Action::Auth(token.clone())),
+                _ => Err(ParseActionError::WrongArgumentCount(lower_action, 1)),
+            },
             "quit" => {
                 if programs.is_empty() {
                     Ok(Action::Quit)
@@ -105,14 +212,79 @@ impl TryFrom<String> for Action {
                     Ok(Action::Stop(programs))
                 }
             }
+            "signal" => match programs.split_first() {
+                Some((signal, programs)) if !programs.is_empty() => {
+                    let signal = signal.parse().map_err(|_| {
+                        ParseActionError::InvalidArgument(lower_action, signal.clone())
+                    })?;
+                    Ok(Action::Signal(signal, programs.to_vec()))
+                }
+                _ => Err(ParseActionError::NoProgramsProvided(lower_action)),
+            },
+            "tail" => parse_tail(lower_action, programs),
             v => Err(ParseActionError::UnrecognizedAction(v.to_string())),
         }
     }
 }
 
+impl TryFrom<String> for Action {
+    type Error = ParseActionError;
+
+    fn try_from(cmd: String) -> Result<Self, Self::Error> {
+        let mut args = cmd.split_whitespace();
+        let Some(action) = args.next() else {
+            return Err(ParseActionError::NoCommandFound);
+        };
+        Action::parse(action, args.map(|e| e.to_string()).collect())
+    }
+}
+
+// tail [-f] [-n N] program[:index] [stdout|stderr]
+fn parse_tail(action: String, args: Vec<String>) -> Result<Action, ParseActionError> {
+    let mut lines = DEFAULT_TAIL_LINES;
+    let mut follow = false;
+    let mut words = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" => follow = true,
+            "-n" => {
+                let Some(count) = args.next() else {
+                    return Err(ParseActionError::InvalidArgument(action, arg));
+                };
+                lines = count
+                    .parse()
+                    .map_err(|_| ParseActionError::InvalidArgument(action.clone(), count))?;
+            }
+            _ => words.push(arg),
+        }
+    }
+
+    let mut words = words.into_iter();
+    let Some(target) = words.next() else {
+        return Err(ParseActionError::NoProgramsProvided(action));
+    };
+    let output = match words.next().as_deref() {
+        None | Some("stdout") => Output::Stdout,
+        Some("stderr") => Output::Stderr,
+        Some(other) => return Err(ParseActionError::InvalidArgument(action, other.to_string())),
+    };
+    if words.next().is_some() {
+        return Err(ParseActionError::ToManyArguments(action));
+    }
+    Ok(Action::Tail {
+        target,
+        output,
+        lines,
+        follow,
+    })
+}
+
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
     #[test]
     fn quit() -> std::result::Result<(), ParseActionError> {
         let action: Action = String::from("quit").try_into()?;
@@ -191,6 +363,100 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn hello() -> std::result::Result<(), ParseActionError> {
+        let action: Action = String::from("Hello").try_into()?;
+        assert_eq!(action, Action::Hello);
+        assert!(matches!(
+            TryInto::<Action>::try_into(String::from("hello there")),
+            Err(ParseActionError::ToManyArguments(_))
+        ));
+        Ok(())
+    }
+    #[test]
+    fn auth() -> std::result::Result<(), ParseActionError> {
+        let action: Action = String::from("auth s3cr3T").try_into()?;
+        assert_eq!(action, Action::Auth("s3cr3T".to_string()));
+        let cpy: Action = action.to_string().try_into()?;
+        assert_eq!(cpy, action);
+        assert!(matches!(
+            TryInto::<Action>::try_into(String::from("auth")),
+            Err(ParseActionError::WrongArgumentCount(_, 1))
+        ));
+        assert!(matches!(
+            TryInto::<Action>::try_into(String::from("auth a b")),
+            Err(ParseActionError::WrongArgumentCount(_, 1))
+        ));
+        Ok(())
+    }
+    #[test]
+    fn signal() -> std::result::Result<(), ParseActionError> {
+        let action: Action = String::from("signal HUP nginx group:web").try_into()?;
+        assert_eq!(
+            action,
+            Action::Signal(
+                Signal::from_str("HUP").unwrap(),
+                vec!["nginx".to_string(), "group:web".to_string()]
+            )
+        );
+        let cpy: Action = action.to_string().try_into()?;
+        assert_eq!(cpy, action);
+        let action: Action = String::from("SIGNAL 10 nginx").try_into()?;
+        assert_eq!(
+            action,
+            Action::Signal(Signal::USR1, vec!["nginx".to_string()])
+        );
+        assert!(matches!(
+            TryInto::<Action>::try_into(String::from("signal HUP")),
+            Err(ParseActionError::NoProgramsProvided(_))
+        ));
+        assert!(matches!(
+            TryInto::<Action>::try_into(String::from("signal NOPE nginx")),
+            Err(ParseActionError::InvalidArgument(_, _))
+        ));
+        Ok(())
+    }
+    #[test]
+    fn tail() -> std::result::Result<(), ParseActionError> {
+        let action: Action = String::from("tail web").try_into()?;
+        assert_eq!(
+            action,
+            Action::Tail {
+                target: "web".to_string(),
+                output: Output::Stdout,
+                lines: 10,
+                follow: false,
+            }
+        );
+        let action: Action = String::from("TAIL -n 5 web:2 stderr -f").try_into()?;
+        assert_eq!(
+            action,
+            Action::Tail {
+                target: "web:2".to_string(),
+                output: Output::Stderr,
+                lines: 5,
+                follow: true,
+            }
+        );
+        let cpy: Action = action.to_string().try_into()?;
+        assert_eq!(cpy, action);
+
+        for (cmd, error) in [
+            ("tail", "NoProgramsProvided"),
+            ("tail -f", "NoProgramsProvided"),
+            ("tail -n", "InvalidArgument"),
+            ("tail -n ten web", "InvalidArgument"),
+            ("tail web stdin", "InvalidArgument"),
+            ("tail web stdout more", "ToManyArguments"),
+        ] {
+            let result = TryInto::<Action>::try_into(String::from(cmd));
+            assert!(
+                format!("{:?}", result).contains(error),
+                "{cmd} gave {result:?}"
+            );
+        }
+        Ok(())
+    }
+    #[test]
     fn unknown_command() -> std::result::Result<(), ParseActionError> {
         let cmd = String::from("Bonjour");
         match TryInto::<Action>::try_into(cmd) {
@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+
+// cgroup of a process, where the processes it starts stay too
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessCgroup {
+    pub path: PathBuf,
+    // signals go to every process of the cgroup
+    pub kill: bool,
+}
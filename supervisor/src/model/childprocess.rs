@@ -1,9 +1,14 @@
+use crate::Notify;
+use crate::ProcessCgroup;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 use std::process::Child;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
 use std::time::Instant;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ChildExitStatus {
     Exited(i32),
     Running,
@@ -11,12 +16,24 @@ pub enum ChildExitStatus {
     WaitError(String),
 }
 
+impl Display for ChildExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChildExitStatus::Exited(code) => write!(f, "{code}"),
+            ChildExitStatus::Running => write!(f, "-"),
+            ChildExitStatus::NonExistent => write!(f, "not started"),
+            ChildExitStatus::WaitError(e) => write!(f, "wait error ({e})"),
+        }
+    }
+}
+
 // https://docs.red-dove.com/supervisor/events.html#process-state-event-type
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ProgramState {
-    // trying to start the process
+    // trying to start the process, until it has been up for startsecs
     Starting,
-    // process has successfully started
+    // process has successfully started (stayed up for startsecs)
     Running,
     // process did not successfully enter the RUNNING state.
     // Taskmaster is going to try to restart it unless it has exceeded its “startretries” configuration limit.
@@ -41,11 +58,13 @@ pub enum ProgramState {
     Error,
     // Restarting
     Restarting,
+    // process is running but failed its last health checks
+    Unhealthy,
 }
 
-impl ToString for ProgramState {
-    fn to_string(&self) -> String {
-        match self {
+impl Display for ProgramState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
             ProgramState::Starting => "starting",
             ProgramState::Running => "running",
             ProgramState::Backoff => "backoff",
@@ -57,11 +76,30 @@ impl ToString for ProgramState {
             ProgramState::Pending => "pending",
             ProgramState::Error => "error",
             ProgramState::Restarting => "restarting",
-        }
-        .to_string()
+            ProgramState::Unhealthy => "unhealthy",
+        };
+        write!(f, "{state}")
     }
 }
 
+// health checks of a running process
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Health {
+    // failed probes in a row
+    pub failures: u8,
+    // when the next probe is due
+    pub next_check: Option<Instant>,
+    // result of the probe running in its own thread, set once it is done
+    pub probe: Option<Arc<OnceLock<bool>>>,
+}
+
+// the signals sent to the whole process group of the process
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupSignals {
+    pub stop: bool,
+    pub kill: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct ChildProcess {
     pub child: Option<Arc<Mutex<Child>>>,
@@ -70,4 +108,15 @@ pub struct ChildProcess {
     pub start_secs: Option<Instant>,
     pub end_time: Option<Instant>,
     pub restart_count: u8,
+    // when the next restart attempt is due, while in backoff
+    pub next_retry: Option<Instant>,
+    pub health: Health,
+    // set when the running process was asked to stop to be restarted,
+    // by its health check or its watchdog
+    pub restart_requested: Option<Instant>,
+    // sd_notify messages of the process
+    pub notify: Notify,
+    // set when the program runs in a cgroup
+    pub cgroup: Option<ProcessCgroup>,
+    pub group_signals: GroupSignals,
 }
@@ -0,0 +1,11 @@
+// user and groups the processes of a program run as
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub gid: u32,
+    // supplementary groups
+    pub groups: Vec<u32>,
+    // name and home directory given in USER, LOGNAME and HOME,
+    // when the program sets a user
+    pub user: Option<(String, String)>,
+}
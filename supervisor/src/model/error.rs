@@ -12,6 +12,12 @@ pub enum Error {
     IoError { message: String },
     WaitError(String),
     ConfigEnvVarNotFound(std::env::VarError),
+    Protocol(String),
+    UnknownPrograms(Vec<String>),
+    DependencyCycle(Vec<String>),
+    Expansion(String),
+    Privileges(String),
+    Cgroup(String),
 }
 
 impl Display for Error {
@@ -25,6 +31,12 @@ impl Display for Error {
             Error::IoError { message } => write!(f, "IO Error : {message}"),
             Error::WaitError(e) => write!(f, "Error waiting for child status : {e}"),
             Error::ConfigEnvVarNotFound(e) => write!(f, "Config env var not found : {e}"),
+            Error::Protocol(e) => write!(f, "Protocol error : {e}"),
+            Error::UnknownPrograms(names) => write!(f, "Unknown programs : {}", names.join(", ")),
+            Error::DependencyCycle(names) => write!(f, "Dependency cycle : {}", names.join(" -> ")),
+            Error::Expansion(e) => write!(f, "Expansion error : {e}"),
+            Error::Privileges(e) => write!(f, "Missing privileges : {e}"),
+            Error::Cgroup(e) => write!(f, "Cgroup error : {e}"),
         }
     }
 }
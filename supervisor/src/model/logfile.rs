@@ -0,0 +1,18 @@
+use std::fs::File;
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+
+use crate::model::Rotation;
+
+// output file of a process written by the supervisor, rotated once
+// it reaches the max size of the rotation
+#[derive(Debug)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub file: File,
+    // size of the current file
+    pub size: u64,
+    pub rotation: Rotation,
+    // compression of the first backup, still running after a rotation
+    pub compressing: Option<JoinHandle<()>>,
+}
@@ -1,11 +1,32 @@
 mod actions;
+mod cgroup;
 mod childprocess;
+mod credentials;
 mod error;
+mod logfile;
+mod notify;
 mod program;
 mod programs;
+mod protocol;
+mod signal;
+mod status;
+mod tail;
 
 pub use actions::{Action, ParseActionError};
-pub use childprocess::{ChildExitStatus, ChildProcess, ProgramState};
+pub use cgroup::ProcessCgroup;
+pub use childprocess::{ChildExitStatus, ChildProcess, GroupSignals, Health, ProgramState};
+pub use credentials::Credentials;
 pub use error::{Error, Result};
-pub use program::{AutoRestart, Origin, Program, StopSignal};
+pub use logfile::LogFile;
+pub use notify::{Notify, NotifySocket};
+#[cfg(test)]
+pub(crate) use program::test_program;
+pub use program::{
+    AutoRestart, Backoff, Cgroup, Cmd, HealthCheck, Limit, Limits, Origin, Probe, Program,
+    Rotation, Watchdog, DEFAULT_PRIORITY, UNLIMITED,
+};
 pub use programs::Programs;
+pub use protocol::{Payload, Request, Response, ResponseStatus, PROTOCOL_VERSION};
+pub use signal::Signal;
+pub use status::{ProcessStatus, ProgramStatus};
+pub use tail::{Follow, Output};
@@ -0,0 +1,24 @@
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+// sd_notify style socket, the process sends `KEY=VALUE` lines to it
+#[derive(Debug)]
+pub struct NotifySocket {
+    pub socket: UnixDatagram,
+    pub path: PathBuf,
+}
+
+// what the process sent on its notify socket
+#[derive(Debug, Clone, Default)]
+pub struct Notify {
+    // given to the process in NOTIFY_SOCKET
+    pub socket: Option<Arc<NotifySocket>>,
+    // READY=1 received
+    pub ready: bool,
+    // last STATUS= received
+    pub status: Option<String>,
+    // last WATCHDOG=1 received
+    pub heartbeat: Option<Instant>,
+}
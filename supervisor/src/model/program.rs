@@ -1,9 +1,11 @@
 use crate::ChildProcess;
+use crate::Signal;
 use serde::{Deserialize, Deserializer};
 
 use std::collections::HashMap;
+use std::fmt::Display;
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Origin {
     CLI,
     Config,
@@ -17,17 +19,321 @@ pub enum AutoRestart {
     Unexpected,
 }
 
-// TODO : Change it or choose enum from libc ?
+// delay between two restart attempts of a process that failed to start:
+// base * multiplier ^ (attempt - 1), capped to max, +/- jitter percent
 #[derive(Debug, Deserialize, PartialEq, Clone)]
-#[serde(rename_all = "UPPERCASE")]
-pub enum StopSignal {
-    Usr1 = 10,
-    Usr2 = 12,
-    Term = 15,
-    Quit = 3,
-    Int = 2,
-    Tin = 21,
-    Prof = 27,
+#[serde(default)]
+pub struct Backoff {
+    // delay in seconds before the first retry
+    pub base: u16,
+    pub multiplier: f64,
+    // maximum delay in seconds
+    pub max: u16,
+    // random part of the delay, between 0 and 1
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            base: 1,
+            multiplier: 2.0,
+            max: 60,
+            jitter: 0.0,
+        }
+    }
+}
+
+// how the health of a process is probed
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum Probe {
+    // command that exits with 0 when the process is healthy
+    #[serde(deserialize_with = "split_cmd_and_args")]
+    Cmd((String, Vec<String>)),
+    // port of localhost accepting connections
+    Tcp(u16),
+    // GET request to localhost answered with a 2xx status
+    Http {
+        port: u16,
+        #[serde(default = "default_http_path")]
+        path: String,
+    },
+}
+
+fn default_http_path() -> String {
+    "/".to_string()
+}
+
+// periodic check of a running process, after `failure_threshold` failed
+// probes in a row the process is unhealthy, and restarted if `restart` is set
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct HealthCheck {
+    #[serde(flatten)]
+    pub probe: Probe,
+    // seconds between two probes
+    #[serde(default = "default_health_interval")]
+    pub interval: u16,
+    // seconds before a probe that didn't answer is a failure
+    #[serde(default = "default_health_timeout")]
+    pub timeout: u16,
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u8,
+    #[serde(default)]
+    pub restart: bool,
+}
+
+fn default_health_interval() -> u16 {
+    10
+}
+
+fn default_health_timeout() -> u16 {
+    3
+}
+
+fn default_failure_threshold() -> u8 {
+    3
+}
+
+// restart a process that hangs without exiting: it has to touch `file`,
+// or send `WATCHDOG=1` to the socket in its NOTIFY_SOCKET env variable
+// when no file is set, at least every `timeout` seconds
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct Watchdog {
+    pub timeout: u16,
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+// the stdout and stderr files are rotated once they reach `maxbytes`,
+// `<path>.1` is the newest of the `backups` rotated files kept
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+pub struct Rotation {
+    // a number of bytes, or a size like "50MB"
+    #[serde(deserialize_with = "deserialize_size")]
+    pub maxbytes: u64,
+    #[serde(default = "default_backups")]
+    pub backups: u8,
+    // gzip the rotated files, `<path>.1.gz`
+    #[serde(default)]
+    pub compress: bool,
+}
+
+fn default_backups() -> u8 {
+    10
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Size {
+    Bytes(u64),
+    Text(String),
+}
+
+fn deserialize_size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let size = match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => bytes,
+        Size::Text(text) => {
+            let text = text.trim();
+            let digits = text
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(text.len());
+            let (number, unit) = text.split_at(digits);
+            let unit = match unit.trim().to_uppercase().as_str() {
+                "" | "B" => 1,
+                "KB" => 1 << 10,
+                "MB" => 1 << 20,
+                "GB" => 1 << 30,
+                unit => {
+                    return Err(serde::de::Error::custom(format!(
+                        "Unknown size unit {unit}"
+                    )))
+                }
+            };
+            number
+                .parse::<u64>()
+                .ok()
+                .and_then(|number| number.checked_mul(unit))
+                .ok_or_else(|| serde::de::Error::custom(format!("Invalid size {text}")))?
+        }
+    };
+    if size == 0 {
+        return Err(serde::de::Error::custom("A size has to be positive"));
+    }
+    Ok(size)
+}
+
+fn deserialize_optional_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_size(deserializer).map(Some)
+}
+
+fn deserialize_cpus<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let cpus = f64::deserialize(deserializer)?;
+    if !cpus.is_finite() || cpus <= 0.0 {
+        return Err(serde::de::Error::custom("cpu_max has to be positive"));
+    }
+    Ok(Some(cpus))
+}
+
+fn default_cgroup_parent() -> String {
+    "/sys/fs/cgroup/taskmaster".to_string()
+}
+
+// cgroup v2 created for the program in `parent`, with a child cgroup per
+// process. The limits are shared by all the processes of the program
+#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Cgroup {
+    #[serde(default = "default_cgroup_parent")]
+    pub parent: String,
+    // a number of bytes, or a size like "512MB"
+    #[serde(default, deserialize_with = "deserialize_optional_size")]
+    pub memory_max: Option<u64>,
+    // number of cpus, can be a fraction like 0.5
+    #[serde(default, deserialize_with = "deserialize_cpus")]
+    pub cpu_max: Option<f64>,
+    #[serde(default)]
+    pub pids_max: Option<u64>,
+    // the stop signal is sent to every process of the cgroup, and what
+    // is left is killed once the process is gone
+    #[serde(default)]
+    pub kill: bool,
+}
+
+// value of a limit without any
+pub const UNLIMITED: u64 = u64::MAX;
+
+// soft and hard value of a resource limit
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Limit {
+    pub soft: u64,
+    pub hard: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LimitValue {
+    Number(u64),
+    Text(String),
+}
+
+// a single value sets both the soft and the hard limit
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LimitSpec {
+    Value(LimitValue),
+    Pair { soft: LimitValue, hard: LimitValue },
+}
+
+impl LimitValue {
+    fn value<E: serde::de::Error>(self) -> Result<u64, E> {
+        match self {
+            LimitValue::Number(value) => Ok(value),
+            LimitValue::Text(text) if text == "unlimited" => Ok(UNLIMITED),
+            LimitValue::Text(text) => Err(E::custom(format!("Invalid limit {text}"))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Limit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (soft, hard) = match LimitSpec::deserialize(deserializer)? {
+            LimitSpec::Value(value) => {
+                let value = value.value()?;
+                (value, value)
+            }
+            LimitSpec::Pair { soft, hard } => (soft.value()?, hard.value()?),
+        };
+        if soft > hard {
+            return Err(serde::de::Error::custom(
+                "The soft limit can't be above the hard one",
+            ));
+        }
+        Ok(Limit { soft, hard })
+    }
+}
+
+// resource limits of the processes, each one a number, "unlimited" or a
+// `soft` and `hard` pair. The ones not set are inherited from the server
+#[derive(Debug, Deserialize, PartialEq, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    // open files
+    pub nofile: Option<Limit>,
+    // processes of the user
+    pub nproc: Option<Limit>,
+    // bytes of the core dumps
+    pub core: Option<Limit>,
+    // bytes of address space
+    #[serde(rename = "as")]
+    pub address_space: Option<Limit>,
+    // seconds of cpu time
+    pub cpu: Option<Limit>,
+    // bytes of stack
+    pub stack: Option<Limit>,
+}
+
+impl Limits {
+    // the limits that are set, named as in the config
+    pub fn entries(&self) -> Vec<(&'static str, Limit)> {
+        [
+            ("nofile", self.nofile),
+            ("nproc", self.nproc),
+            ("core", self.core),
+            ("as", self.address_space),
+            ("cpu", self.cpu),
+            ("stack", self.stack),
+        ]
+        .into_iter()
+        .filter_map(|(name, limit)| limit.map(|limit| (name, limit)))
+        .collect()
+    }
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |value: u64| match value {
+            UNLIMITED => "unlimited".to_string(),
+            value => value.to_string(),
+        };
+        if self.soft == self.hard {
+            write!(f, "{}", value(self.soft))
+        } else {
+            write!(f, "{}:{}", value(self.soft), value(self.hard))
+        }
+    }
+}
+
+// `name=value` separated by spaces, `soft:hard` when they differ
+impl Display for Limits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (name, limit)) in self.entries().into_iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{name}={limit}")?;
+        }
+        Ok(())
+    }
+}
+
+// priority of the programs that don't set one
+pub const DEFAULT_PRIORITY: u16 = 999;
+
+fn default_priority() -> u16 {
+    DEFAULT_PRIORITY
 }
 
 // default umask
@@ -48,15 +354,137 @@ where
     }
 }
 
+// split a command line into words like a POSIX shell would: words are
+// separated by blanks, a backslash escapes the next character, single
+// quotes keep everything as is and double quotes only let a backslash
+// escape `$`, `` ` ``, `"`, `\` and newlines
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    // None between two words, an empty quoted string is still a word
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(word) = word.take() {
+                    words.push(word);
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => (),
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("Trailing backslash".to_string()),
+            },
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => (),
+                            Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// quote a word so the shell reads it back as is
+fn quote_word(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+// command of a program, given as a line or a list of words
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cmd {
+    pub program: String,
+    pub args: Vec<String>,
+    // the line as written in the config, what `/bin/sh -c` runs in shell mode
+    pub line: String,
+}
+
+impl From<(String, Vec<String>)> for Cmd {
+    fn from((program, args): (String, Vec<String>)) -> Self {
+        let line = std::iter::once(&program)
+            .chain(&args)
+            .map(|word| quote_word(word))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Cmd {
+            program,
+            args,
+            line,
+        }
+    }
+}
+
+// a YAML list is used as is, a string is split into words
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CmdSpec {
+    Line(String),
+    List(Vec<String>),
+}
+
 fn split_cmd_and_args<'de, D>(deserializer: D) -> Result<(String, Vec<String>), D::Error>
 where
     D: Deserializer<'de>,
 {
-    let s: String = Deserialize::deserialize(deserializer)?;
-    let mut split = s.split_whitespace();
-    let cmd = split.next().unwrap_or("").to_string();
-    let args = split.map(String::from).collect();
-    Ok((cmd, args))
+    let cmd = deserialize_cmd(deserializer)?;
+    Ok((cmd.program, cmd.args))
+}
+
+fn deserialize_cmd<'de, D>(deserializer: D) -> Result<Cmd, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let (words, line) = match CmdSpec::deserialize(deserializer)? {
+        CmdSpec::Line(line) => (
+            split_words(&line).map_err(serde::de::Error::custom)?,
+            Some(line),
+        ),
+        CmdSpec::List(words) => (words, None),
+    };
+    let mut words = words.into_iter();
+    let Some(program) = words.next() else {
+        return Err(serde::de::Error::custom("Empty command"));
+    };
+    let cmd = Cmd::from((program, words.collect()));
+    Ok(match line {
+        Some(line) => Cmd { line, ..cmd },
+        None => cmd,
+    })
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -65,8 +493,12 @@ pub struct Program {
     pub name: String,
 
     // command to execute and its arguments
-    #[serde(deserialize_with = "split_cmd_and_args")]
-    pub cmd: (String, Vec<String>),
+    #[serde(deserialize_with = "deserialize_cmd")]
+    pub cmd: Cmd,
+
+    // run the command line through `/bin/sh -c`, for pipes, redirections...
+    #[serde(default)]
+    pub shell: bool,
 
     // number of process to start
     #[serde(alias = "numprocs")]
@@ -102,7 +534,7 @@ pub struct Program {
 
     // signal sent by job control to all the process to stop it
     #[serde(alias = "stopsignal")]
-    pub stop_signal: StopSignal,
+    pub stop_signal: Signal,
 
     // number of seconds to wait before sending a SIGKILL
     // to all the process
@@ -110,6 +542,18 @@ pub struct Program {
     #[serde(alias = "stoptime")]
     pub stop_time: u16,
 
+    // send the stop signal to the whole process group of the process,
+    // implies kill_as_group
+    #[serde(default, alias = "stopasgroup")]
+    pub stop_as_group: bool,
+    // send the SIGKILL after stoptime to the whole process group
+    #[serde(default, alias = "killasgroup")]
+    pub kill_as_group: bool,
+
+    // delay between the restart attempts of a process
+    #[serde(default)]
+    pub backoff: Backoff,
+
     // environment variables to set
     pub env: Option<HashMap<String, String>>,
 
@@ -124,10 +568,58 @@ pub struct Program {
     )]
     pub umask: String,
 
+    // user the processes run as, by name or uid, the server has to run as root
+    #[serde(default)]
+    pub user: Option<String>,
+    // group the processes run as, by name or gid, the one of the user by default
+    #[serde(default)]
+    pub group: Option<String>,
+    // the groups of the user by default
+    #[serde(default)]
+    pub supplementary_groups: Vec<String>,
+
+    // resource limits applied before exec
+    #[serde(default)]
+    pub limits: Limits,
+    // cgroup v2 the processes run in, with its limits
+    #[serde(default)]
+    pub cgroup: Option<Cgroup>,
+
     // stdout and stderr redirection
     pub stdout: String,
     pub stderr: String,
 
+    // append to stdout and stderr instead of truncating them at each start
+    #[serde(default)]
+    pub append: bool,
+
+    // rotate stdout and stderr, the output then goes through the supervisor
+    #[serde(default)]
+    pub rotation: Option<Rotation>,
+
+    // programs that must be running before this one is started,
+    // this one is stopped before them
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    // programs with a low priority are started first and stopped last
+    // default : 999
+    #[serde(default = "default_priority")]
+    pub priority: u16,
+
+    // checks that a running process does its job, not only that it is alive
+    #[serde(default)]
+    pub healthcheck: Option<HealthCheck>,
+
+    // heartbeat the process has to give to not be considered hung
+    #[serde(default)]
+    pub watchdog: Option<Watchdog>,
+
+    // the process is given a NOTIFY_SOCKET and is only running
+    // once it sent READY=1 on it, like systemd Type=notify services
+    #[serde(default)]
+    pub notify: bool,
+
     // below part is internal, it will contain all the state fields
     // used by the supervisor to manage the program
 
@@ -137,7 +629,53 @@ pub struct Program {
     pub children: Vec<ChildProcess>,
 }
 
+// a program running `cmd`, split like a line of the config, once and
+// without output. Tests only set the fields they exercise on top of it
 #[cfg(test)]
+pub(crate) fn test_program(name: &str, cmd: &str) -> Program {
+    let mut words = split_words(cmd).unwrap().into_iter();
+    let program = words.next().unwrap();
+    Program {
+        name: name.to_string(),
+        cmd: Cmd {
+            line: cmd.to_string(),
+            ..Cmd::from((program, words.collect()))
+        },
+        shell: false,
+        num_procs: 1,
+        auto_start: false,
+        auto_restart: AutoRestart::Never,
+        exitcodes: vec![0],
+        start_retries: 0,
+        start_secs: 0,
+        stop_signal: Signal::TERM,
+        stop_time: 1,
+        stop_as_group: false,
+        kill_as_group: false,
+        backoff: Backoff::default(),
+        env: None,
+        working_dir: ".".to_string(),
+        umask: "0o022".to_string(),
+        user: None,
+        group: None,
+        supplementary_groups: vec![],
+        limits: Limits::default(),
+        cgroup: None,
+        stdout: "/dev/null".to_string(),
+        stderr: "/dev/null".to_string(),
+        append: false,
+        rotation: None,
+        depends_on: vec![],
+        priority: DEFAULT_PRIORITY,
+        healthcheck: None,
+        watchdog: None,
+        notify: false,
+        children: vec![],
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -157,6 +695,44 @@ mod tests {
         assert_eq!(cmd.1, vec!["-c", "/etc/nginx/test.conf"]);
     }
 
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words(r#"echo "hello world" 'it''s' a\ b """#).unwrap(),
+            vec!["echo", "hello world", "its", "a b", ""]
+        );
+        assert_eq!(
+            split_words(r#"sh -c 'echo "$HOME"' "\$x \n""#).unwrap(),
+            vec!["sh", "-c", "echo \"$HOME\"", "$x \\n"]
+        );
+        assert_eq!(split_words("  a\tb  ").unwrap(), vec!["a", "b"]);
+        assert!(split_words("echo \\").is_err());
+        assert!(split_words("echo 'oops").is_err());
+        assert!(split_words("echo \"oops").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_cmd() {
+        let deserializer =
+            serde_yaml::Deserializer::from_str(r#"["/bin/echo", "hello world", "it's"]"#);
+        let cmd = deserialize_cmd(deserializer).unwrap();
+        assert_eq!(cmd.program, "/bin/echo");
+        assert_eq!(cmd.args, vec!["hello world", "it's"]);
+        assert_eq!(cmd.line, r#"/bin/echo 'hello world' 'it'\''s'"#);
+
+        // the line is kept as written for the shell
+        let line = r#"'echo hello | tr a-z A-Z > "/tmp/out file"'"#;
+        let deserializer = serde_yaml::Deserializer::from_str(line);
+        let cmd = deserialize_cmd(deserializer).unwrap();
+        assert_eq!(cmd.line, r#"echo hello | tr a-z A-Z > "/tmp/out file""#);
+        assert_eq!(cmd.args.last().unwrap(), "/tmp/out file");
+
+        for cmd in ["''", "[]", "'echo \"oops'"] {
+            let deserializer = serde_yaml::Deserializer::from_str(cmd);
+            assert!(deserialize_cmd(deserializer).is_err());
+        }
+    }
+
     #[test]
     fn test_program_deserialization() {
         let yaml = r#"
@@ -177,16 +753,17 @@ mod tests {
         "#;
 
         let program: Program = serde_yaml::from_str(yaml).unwrap();
-        assert_eq!(program.cmd.0, "/usr/local/bin/nginx");
-        assert_eq!(program.cmd.1, vec!["-c", "/etc/nginx/test.conf"]);
+        assert_eq!(program.cmd.program, "/usr/local/bin/nginx");
+        assert_eq!(program.cmd.args, vec!["-c", "/etc/nginx/test.conf"]);
         assert_eq!(program.num_procs, 1);
         assert_eq!(program.auto_start, true);
         assert_eq!(program.auto_restart, AutoRestart::Always);
         assert_eq!(program.exitcodes, [0]);
         assert_eq!(program.start_retries, 3);
         assert_eq!(program.start_secs, 10);
-        assert_eq!(program.stop_signal, StopSignal::Term);
+        assert_eq!(program.stop_signal, Signal::TERM);
         assert_eq!(program.stop_time, 10);
+        assert_eq!(program.backoff, Backoff::default());
 
         let mut expected_env = HashMap::new();
         expected_env.insert("key".to_string(), "value".to_string());
@@ -212,22 +789,25 @@ mod tests {
     startsecs: 15
     stopsignal: "USR1"
     stoptime: 20
+    stopasgroup: true
     env: {"key1": "value1", "key2": "value2"}
     stdout: ""
     stderr: ""
     "#;
 
         let program: Program = serde_yaml::from_str(yaml).unwrap();
-        assert_eq!(program.cmd.0, "/usr/local/bin/nginx");
-        assert!(program.cmd.1.is_empty());
+        assert_eq!(program.cmd.program, "/usr/local/bin/nginx");
+        assert!(program.cmd.args.is_empty());
         assert_eq!(program.num_procs, 2);
         assert_eq!(program.auto_start, false);
         assert_eq!(program.auto_restart, AutoRestart::Never);
         assert_eq!(program.exitcodes, [0, 1, 2]);
         assert_eq!(program.start_retries, 5);
         assert_eq!(program.start_secs, 15);
-        assert_eq!(program.stop_signal, StopSignal::Usr1);
+        assert_eq!(program.stop_signal, Signal::USR1);
         assert_eq!(program.stop_time, 20);
+        assert!(program.stop_as_group);
+        assert!(!program.kill_as_group);
 
         let mut expected_env = HashMap::new();
         expected_env.insert("key1".to_string(), "value1".to_string());
@@ -239,6 +819,200 @@ mod tests {
         assert_eq!(program.stderr, "".to_string());
     }
 
+    #[test]
+    fn test_program_deserialization_backoff() {
+        let yaml = r#"
+    cmd: "/usr/local/bin/nginx"
+    numprocs: 1
+    autostart: true
+    autorestart: "unexpected"
+    exitcodes: [0]
+    startretries: 3
+    workingdir: "/tmp"
+    startsecs: 1
+    stopsignal: "TERM"
+    stoptime: 1
+    backoff:
+      base: 2
+      max: 30
+    env: {}
+    stdout: ""
+    stderr: ""
+    "#;
+
+        let program: Program = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(program.backoff.base, 2);
+        assert_eq!(program.backoff.multiplier, 2.0);
+        assert_eq!(program.backoff.max, 30);
+        assert_eq!(program.backoff.jitter, 0.0);
+    }
+
+    #[test]
+    fn test_program_deserialization_priority() {
+        let yaml = r#"
+    cmd: "/usr/local/bin/nginx"
+    numprocs: 1
+    autostart: true
+    autorestart: "unexpected"
+    exitcodes: [0]
+    startretries: 3
+    workingdir: "/tmp"
+    startsecs: 1
+    stopsignal: "TERM"
+    stoptime: 1
+    env: {}
+    stdout: ""
+    stderr: ""
+    "#;
+
+        let program: Program = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(program.priority, DEFAULT_PRIORITY);
+        assert!(program.depends_on.is_empty());
+
+        let yaml = format!("{yaml}\n    priority: 10\n    depends_on: [db]");
+        let program: Program = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(program.priority, 10);
+        assert_eq!(program.depends_on, vec!["db"]);
+    }
+
+    #[test]
+    fn test_program_deserialization_healthcheck() {
+        let yaml = r#"
+    cmd: "/usr/local/bin/nginx"
+    numprocs: 1
+    autostart: true
+    autorestart: "unexpected"
+    exitcodes: [0]
+    startretries: 3
+    workingdir: "/tmp"
+    startsecs: 1
+    stopsignal: "TERM"
+    stoptime: 1
+    env: {}
+    stdout: ""
+    stderr: ""
+    "#;
+
+        let program: Program = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(program.healthcheck, None);
+
+        let with_check = |check: &str| {
+            let yaml = format!("{yaml}\n    healthcheck:\n{check}");
+            serde_yaml::from_str::<Program>(&yaml).map(|p| p.healthcheck.unwrap())
+        };
+
+        let check = with_check("      cmd: /usr/bin/curl -f localhost\n      interval: 5").unwrap();
+        assert_eq!(
+            check.probe,
+            Probe::Cmd((
+                "/usr/bin/curl".to_string(),
+                vec!["-f".to_string(), "localhost".to_string()]
+            ))
+        );
+        assert_eq!(check.interval, 5);
+        assert_eq!(check.timeout, 3);
+        assert_eq!(check.failure_threshold, 3);
+        assert!(!check.restart);
+
+        let check = with_check("      tcp: 8080\n      restart: true").unwrap();
+        assert_eq!(check.probe, Probe::Tcp(8080));
+        assert!(check.restart);
+
+        let check =
+            with_check("      http:\n        port: 80\n      failure_threshold: 1").unwrap();
+        assert_eq!(
+            check.probe,
+            Probe::Http {
+                port: 80,
+                path: "/".to_string()
+            }
+        );
+        assert_eq!(check.failure_threshold, 1);
+
+        assert!(with_check("      interval: 5").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_size() {
+        let rotation = |maxbytes: &str| {
+            serde_yaml::from_str::<Rotation>(&format!("maxbytes: {maxbytes}"))
+                .map(|rotation| rotation.maxbytes)
+        };
+        assert_eq!(rotation("1024").unwrap(), 1024);
+        assert_eq!(rotation("'10KB'").unwrap(), 10 * 1024);
+        assert_eq!(rotation("50MB").unwrap(), 50 * 1024 * 1024);
+        assert_eq!(rotation("1 gb").unwrap(), 1024 * 1024 * 1024);
+        assert!(rotation("0").is_err());
+        assert!(rotation("12TB").is_err());
+        assert!(rotation("MB").is_err());
+
+        let rotation: Rotation = serde_yaml::from_str("maxbytes: 1MB").unwrap();
+        assert_eq!(rotation.backups, 10);
+        assert!(!rotation.compress);
+    }
+
+    #[test]
+    fn test_deserialize_limits() {
+        let limits: Limits =
+            serde_yaml::from_str("nofile: 1024\ncore: unlimited\nas: {soft: 100, hard: unlimited}")
+                .unwrap();
+        assert_eq!(
+            limits.nofile,
+            Some(Limit {
+                soft: 1024,
+                hard: 1024
+            })
+        );
+        assert_eq!(limits.core.map(|limit| limit.hard), Some(UNLIMITED));
+        assert_eq!(limits.nproc, None);
+        assert_eq!(
+            limits.to_string(),
+            "nofile=1024 core=unlimited as=100:unlimited"
+        );
+        assert_eq!(Limits::default().to_string(), "");
+
+        let limits = |yaml: &str| serde_yaml::from_str::<Limits>(yaml);
+        assert!(limits("cpu: forever").is_err());
+        assert!(limits("stack: {soft: 10, hard: 5}").is_err());
+        assert!(limits("files: 10").is_err());
+    }
+
+    #[test]
+    fn test_program_deserialization_watchdog() {
+        let yaml = r#"
+    cmd: "/usr/local/bin/worker"
+    numprocs: 1
+    autostart: true
+    autorestart: "unexpected"
+    exitcodes: [0]
+    startretries: 3
+    workingdir: "/tmp"
+    startsecs: 1
+    stopsignal: "TERM"
+    stoptime: 1
+    env: {}
+    stdout: ""
+    stderr: ""
+    watchdog:
+      timeout: 30
+      file: /tmp/worker.heartbeat
+    "#;
+
+        let program: Program = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            program.watchdog,
+            Some(Watchdog {
+                timeout: 30,
+                file: Some("/tmp/worker.heartbeat".to_string())
+            })
+        );
+        assert!(!program.notify);
+
+        let yaml = format!("{yaml}\n    notify: true");
+        let program: Program = serde_yaml::from_str(&yaml).unwrap();
+        assert!(program.notify);
+    }
+
     #[test]
     fn test_deserialize_octal_string() {
         // Test a valid octal string
@@ -1,8 +1,21 @@
-use crate::Program;
+use crate::{Origin, Program};
 use serde::Deserialize;
 use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, Default)]
 pub struct Programs {
     pub programs: HashMap<String, Program>,
+    // group name to the programs it contains, targeted with `group:*` or `group:member`
+    #[serde(default)]
+    pub groups: HashMap<String, Vec<String>>,
+
+    // programs waiting for their dependencies to be running before
+    // being started, in start order
+    #[serde(skip)]
+    pub pending: Vec<(String, Origin)>,
+
+    // old instances of the programs a reload restarted or removed, and
+    // of their extra processes, until their processes are gone
+    #[serde(skip)]
+    pub stopping: Vec<Program>,
 }
@@ -0,0 +1,267 @@
+// Newline-delimited JSON protocol between supervisor_ctl and the server.
+// Every request is answered by exactly one response with the same id,
+// except `tail -f`: its responses have `more` set until the client sends
+// another request, then a last one without it ends the stream.
+use crate::{Action, Error, ParseActionError, ProgramStatus, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    pub id: u64,
+    pub action: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Payload {
+    Message(String),
+    Status(Vec<ProgramStatus>),
+    // output of a process, as written by it
+    Output(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    pub id: u64,
+    pub status: ResponseStatus,
+    #[serde(default)]
+    pub payload: Option<Payload>,
+    #[serde(default)]
+    pub error: Option<String>,
+    // other responses with the same id follow
+    #[serde(default)]
+    pub more: bool,
+}
+
+impl Display for Payload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Payload::Message(message) => write!(f, "{message}"),
+            // printed on its own line, the last newline would leave an empty one
+            Payload::Output(output) => write!(f, "{}", output.strip_suffix('\n').unwrap_or(output)),
+            Payload::Status(programs) => {
+                for (i, program) in programs.iter().enumerate() {
+                    if i != 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{program}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// one json object per line
+fn encode<T: Serialize>(value: &T) -> Result<String> {
+    let mut line = serde_json::to_string(value).map_err(|e| Error::Protocol(e.to_string()))?;
+    line.push('\n');
+    Ok(line)
+}
+
+fn decode<'a, T: Deserialize<'a>>(line: &'a str) -> Result<T> {
+    serde_json::from_str(line.trim_end()).map_err(|e| Error::Protocol(e.to_string()))
+}
+
+impl Request {
+    pub fn new(id: u64, action: &Action) -> Self {
+        Request {
+            version: PROTOCOL_VERSION,
+            id,
+            action: action.name().to_string(),
+            args: action.args(),
+        }
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        encode(self)
+    }
+
+    pub fn decode(line: &str) -> Result<Self> {
+        decode(line)
+    }
+
+    pub fn action(&self) -> std::result::Result<Action, ParseActionError> {
+        Action::parse(&self.action, self.args.clone())
+    }
+}
+
+impl Response {
+    pub fn ok(id: u64, payload: Payload) -> Self {
+        Response {
+            version: PROTOCOL_VERSION,
+            id,
+            status: ResponseStatus::Ok,
+            payload: Some(payload),
+            error: None,
+            more: false,
+        }
+    }
+
+    // part of a streamed answer, more responses follow
+    pub fn chunk(id: u64, output: String) -> Self {
+        Response {
+            more: true,
+            ..Response::ok(id, Payload::Output(output))
+        }
+    }
+
+    pub fn error<S: Display>(id: u64, error: S) -> Self {
+        Response {
+            version: PROTOCOL_VERSION,
+            id,
+            status: ResponseStatus::Error,
+            payload: None,
+            error: Some(error.to_string()),
+            more: false,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == ResponseStatus::Ok
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        encode(self)
+    }
+
+    pub fn decode(line: &str) -> Result<Self> {
+        decode(line)
+    }
+}
+
+impl Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.payload, &self.error) {
+            (_, Some(error)) => write!(f, "{error}"),
+            (Some(payload), None) => write!(f, "{payload}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChildExitStatus, ProcessStatus, ProgramState};
+
+    #[test]
+    fn request_round_trip() -> Result<()> {
+        let action = Action::Restart(vec!["web".to_string(), "worker".to_string()]);
+        let request = Request::new(3, &action);
+        assert_eq!(request.action, "restart");
+        assert_eq!(request.args, vec!["web", "worker"]);
+
+        let line = request.encode()?;
+        assert!(line.ends_with('\n'));
+        let decoded = Request::decode(&line)?;
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.action().unwrap(), action);
+        Ok(())
+    }
+
+    #[test]
+    fn request_args_with_whitespaces() -> Result<()> {
+        let actions = [
+            Action::Auth("s3cr3T with spaces".to_string()),
+            Action::Stop(vec!["my web".to_string(), "worker".to_string()]),
+            Action::Tail {
+                target: "my web:1".to_string(),
+                output: crate::Output::Stderr,
+                lines: 5,
+                follow: true,
+            },
+        ];
+        for action in actions {
+            let request = Request::decode(&Request::new(1, &action).encode()?)?;
+            assert_eq!(request.action().unwrap(), action);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn request_without_args() -> Result<()> {
+        let request = Request::decode(r#"{"version": 1, "id": 1, "action": "status"}"#)?;
+        assert_eq!(request.action().unwrap(), Action::Status);
+        Ok(())
+    }
+
+    #[test]
+    fn request_invalid() {
+        assert!(matches!(Request::decode("status"), Err(Error::Protocol(_))));
+    }
+
+    #[test]
+    fn response_multiline_payload() -> Result<()> {
+        let status = vec![ProgramStatus {
+            name: "web".to_string(),
+            processes: vec![
+                ProcessStatus {
+                    index: 0,
+                    state: ProgramState::Running,
+                    pid: Some(42),
+                    uptime: Some(3),
+                    exit_status: ChildExitStatus::Running,
+                    restarts: 0,
+                    next_retry: None,
+                    status_text: None,
+                },
+                ProcessStatus {
+                    index: 1,
+                    state: ProgramState::Fatal,
+                    pid: None,
+                    uptime: None,
+                    exit_status: ChildExitStatus::Exited(2),
+                    restarts: 3,
+                    next_retry: None,
+                    status_text: None,
+                },
+            ],
+            limits: None,
+        }];
+        let response = Response::ok(7, Payload::Status(status));
+
+        // the whole response stays on a single line
+        let line = response.encode()?;
+        assert_eq!(line.lines().count(), 1);
+        let decoded = Response::decode(&line)?;
+        assert_eq!(decoded, response);
+        assert!(decoded.is_ok());
+        assert_eq!(decoded.to_string().lines().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn response_chunk() -> Result<()> {
+        let response = Response::decode(&Response::chunk(4, "a\nb\n".to_string()).encode()?)?;
+        assert!(response.is_ok() && response.more);
+        assert_eq!(response.to_string(), "a\nb");
+        // responses of older servers
+        let response = Response::decode(r#"{"version": 1, "id": 1, "status": "ok"}"#)?;
+        assert!(!response.more);
+        Ok(())
+    }
+
+    #[test]
+    fn response_error() -> Result<()> {
+        let response = Response::decode(&Response::error(1, "unknown program").encode()?)?;
+        assert!(!response.is_ok());
+        assert_eq!(response.payload, None);
+        assert_eq!(response.to_string(), "unknown program");
+        Ok(())
+    }
+}
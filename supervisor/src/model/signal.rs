@@ -0,0 +1,142 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+// names of the signals, without their SIG prefix
+const SIGNALS: [(&str, libc::c_int); 29] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+// a signal, given by its name like "TERM" or "SIGHUP", or by its number
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Signal(u8);
+
+impl Signal {
+    pub const INT: Signal = Signal(libc::SIGINT as u8);
+    pub const KILL: Signal = Signal(libc::SIGKILL as u8);
+    pub const TERM: Signal = Signal(libc::SIGTERM as u8);
+    pub const USR1: Signal = Signal(libc::SIGUSR1 as u8);
+
+    pub fn number(self) -> u8 {
+        self.0
+    }
+}
+
+impl FromStr for Signal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Unknown signal {s}");
+        if let Ok(number) = s.parse::<libc::c_int>() {
+            return match number {
+                1.. if number <= libc::SIGRTMAX() => Ok(Signal(number as u8)),
+                _ => Err(invalid()),
+            };
+        }
+        let name = s.to_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        SIGNALS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|&(_, number)| Signal(number as u8))
+            .ok_or_else(invalid)
+    }
+}
+
+// the name when it has one, the number otherwise
+impl Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match SIGNALS
+            .iter()
+            .find(|&&(_, number)| number == libc::c_int::from(self.0))
+        {
+            Some((name, _)) => write!(f, "{name}"),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignalSpec {
+    Number(u64),
+    Name(String),
+}
+
+impl<'de> Deserialize<'de> for Signal {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let signal = match SignalSpec::deserialize(deserializer)? {
+            SignalSpec::Number(number) => number.to_string(),
+            SignalSpec::Name(name) => name,
+        };
+        signal.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!("TERM".parse(), Ok(Signal::TERM));
+        assert_eq!("sigterm".parse(), Ok(Signal::TERM));
+        assert_eq!("15".parse(), Ok(Signal::TERM));
+        assert_eq!("TTIN".parse::<Signal>().map(Signal::number), Ok(21));
+        assert_eq!(
+            "hup".parse::<Signal>().map(|s| s.to_string()),
+            Ok("HUP".into())
+        );
+        // real time signals have no name
+        let rtmin = libc::SIGRTMIN().to_string();
+        assert_eq!(rtmin.parse::<Signal>().map(|s| s.to_string()), Ok(rtmin));
+        assert!("0".parse::<Signal>().is_err());
+        assert!("-9".parse::<Signal>().is_err());
+        assert!((libc::SIGRTMAX() + 1)
+            .to_string()
+            .parse::<Signal>()
+            .is_err());
+        assert!("TIN".parse::<Signal>().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_signal() {
+        let signal = |yaml: &str| serde_yaml::from_str::<Signal>(yaml);
+        assert_eq!(signal("USR1").unwrap(), Signal::USR1);
+        assert_eq!(signal("SIGINT").unwrap(), Signal::INT);
+        assert_eq!(signal("9").unwrap(), Signal::KILL);
+        assert!(signal("NOPE").is_err());
+        assert!(signal("1000").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{ChildExitStatus, ProgramState};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessStatus {
+    pub index: usize,
+    pub state: ProgramState,
+    // only set while the process is alive
+    pub pid: Option<u32>,
+    // seconds since the process was (re)started, while it is alive
+    pub uptime: Option<u64>,
+    pub exit_status: ChildExitStatus,
+    pub restarts: u8,
+    // seconds before the next restart attempt, while in backoff
+    pub next_retry: Option<f64>,
+    // last STATUS= sent on the notify socket, while the process is alive
+    #[serde(default)]
+    pub status_text: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgramStatus {
+    pub name: String,
+    // empty when the program is inactive
+    pub processes: Vec<ProcessStatus>,
+    // resource limits of the processes, when the program sets some
+    #[serde(default)]
+    pub limits: Option<String>,
+}
+
+// uptime displayed as h:mm:ss
+fn format_uptime(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+// one line per process, `name:index` first, then the limits
+impl Display for ProgramStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.processes.is_empty() {
+            write!(f, "{} : Inactive program", self.name)?;
+        }
+        for (i, process) in self.processes.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}:{} {:<10} pid {:<7} uptime {:<9} exit status {:<11} restarts {}",
+                self.name,
+                process.index,
+                process.state.to_string(),
+                or_dash(process.pid),
+                or_dash(process.uptime.map(format_uptime)),
+                process.exit_status.to_string(),
+                process.restarts
+            )?;
+            if let Some(delay) = process.next_retry {
+                write!(f, " (next retry in {:.1}s)", delay)?;
+            }
+            if let Some(text) = &process.status_text {
+                write!(f, " - {}", text)?;
+            }
+        }
+        if let Some(limits) = &self.limits {
+            write!(f, "\n{} limits {}", self.name, limits)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(0), "0:00:00");
+        assert_eq!(format_uptime(83), "0:01:23");
+        assert_eq!(format_uptime(3 * 3600 + 61), "3:01:01");
+    }
+
+    #[test]
+    fn test_display_inactive() {
+        let status = ProgramStatus {
+            name: "web".to_string(),
+            processes: vec![],
+            limits: None,
+        };
+        assert_eq!(status.to_string(), "web : Inactive program");
+    }
+
+    #[test]
+    fn test_display_processes() {
+        let status = ProgramStatus {
+            name: "web".to_string(),
+            processes: vec![
+                ProcessStatus {
+                    index: 0,
+                    state: ProgramState::Running,
+                    pid: Some(42),
+                    uptime: Some(61),
+                    exit_status: ChildExitStatus::Running,
+                    restarts: 0,
+                    next_retry: None,
+                    status_text: Some("Serving 3 clients".to_string()),
+                },
+                ProcessStatus {
+                    index: 1,
+                    state: ProgramState::Backoff,
+                    pid: None,
+                    uptime: None,
+                    exit_status: ChildExitStatus::Exited(1),
+                    restarts: 2,
+                    next_retry: Some(1.5),
+                    status_text: None,
+                },
+            ],
+            limits: Some("nofile=1024 core=0:unlimited".to_string()),
+        };
+        let display = status.to_string();
+        let lines = display.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("web:0 running"));
+        assert!(lines[0].contains("pid 42"));
+        assert!(lines[0].contains("uptime 0:01:01"));
+        assert!(lines[0].ends_with(" - Serving 3 clients"));
+        assert!(lines[1].starts_with("web:1 backoff"));
+        assert!(lines[1].contains("exit status 1"));
+        assert!(lines[1].contains("restarts 2"));
+        assert!(lines[1].ends_with("(next retry in 1.5s)"));
+        assert_eq!(lines[2], "web limits nofile=1024 core=0:unlimited");
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+// output of a process that can be tailed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Stdout,
+    Stderr,
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Output::Stdout => write!(f, "stdout"),
+            Output::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+// output file of a process followed by a client, `offset` is where
+// the next read starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Follow {
+    pub path: PathBuf,
+    pub offset: u64,
+}
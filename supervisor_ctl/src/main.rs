@@ -1,34 +1,59 @@
 mod model;
 mod supervisor_ctl;
 
+use clap::Parser;
 use daemonize::Result;
-use std::io::{BufReader, BufWriter};
+use model::Args;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::net::TcpStream;
-use supervisor_ctl::supervisor_ctl;
+use std::os::unix::net::UnixStream;
+use supervisor_ctl::{one_shot, supervisor_ctl};
 struct ClientContext {
-    pub(crate) writer: BufWriter<TcpStream>,
-    pub(crate) reader: BufReader<TcpStream>,
+    pub(crate) writer: Box<dyn Write>,
+    pub(crate) reader: Box<dyn BufRead>,
+    pub(crate) request_id: u64,
 }
 
-fn main() -> Result<()> {
-    let addr = match std::env::var("SERVER_ADDRESS") {
-        Ok(addr) => addr,
-        Err(_) => {
-            logger::log(
-                "SERVER_ADDRESS environment variable is not set, using localhost:4242 default\n",
-                logger::LogInfo::Error,
-            )?;
-            "127.0.0.1:4242".to_string()
-        }
+/// Connect to the unix socket from `SERVER_SOCKET_PATH` if set, otherwise to `SERVER_ADDRESS`
+fn connect() -> Result<ClientContext> {
+    let (writer, reader): (Box<dyn Write>, Box<dyn BufRead>) = if let Ok(path) =
+        std::env::var("SERVER_SOCKET_PATH")
+    {
+        let client = UnixStream::connect(path)?;
+        (
+            Box::new(BufWriter::new(client.try_clone()?)),
+            Box::new(BufReader::new(client)),
+        )
+    } else {
+        let addr = match std::env::var("SERVER_ADDRESS") {
+            Ok(addr) => addr,
+            Err(_) => {
+                logger::log(
+                        "SERVER_ADDRESS environment variable is not set, using localhost:4242 default\n",
+                        logger::LogInfo::Error,
+                    )?;
+                "127.0.0.1:4242".to_string()
+            }
+        };
+        let client = TcpStream::connect(addr)?;
+        (
+            Box::new(BufWriter::new(client.try_clone()?)),
+            Box::new(BufReader::new(client)),
+        )
     };
-    let client = TcpStream::connect(addr)?;
+    Ok(ClientContext {
+        writer,
+        reader,
+        request_id: 0,
+    })
+}
 
-    let stream_writer = BufWriter::new(client.try_clone()?);
-    let stream_reader = BufReader::new(client.try_clone()?);
-    if let Err(e) = supervisor_ctl(ClientContext {
-        writer: stream_writer,
-        reader: stream_reader,
-    }) {
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if !args.command.is_empty() {
+        std::process::exit(one_shot(args.command, connect));
+    }
+    if let Err(e) = supervisor_ctl(connect()?) {
         eprintln!("{}", e)
     }
     Ok(())
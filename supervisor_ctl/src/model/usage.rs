@@ -1,7 +1,12 @@
 use clap::Parser;
 
+/// Control the taskmaster server, starts an interactive shell when no command is given
 #[derive(Parser, Debug)]
+#[clap(trailing_var_arg = true)]
 pub struct Args {
-    /// Path to the fiel containing the initial config
-    pub path: String,
+    /// Command to run once before exiting, e.g. `status` or `restart web worker`.
+    /// Exits with 0 on success, 1 if the server failed to run it,
+    /// 2 if the command is invalid and 3 if the server can't be reached or refused the client
+    #[clap(allow_hyphen_values = true)]
+    pub command: Vec<String>,
 }
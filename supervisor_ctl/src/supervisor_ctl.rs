@@ -1,24 +1,175 @@
-use std::io::{BufRead, Write};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use daemonize::Result;
-use reedline_repl_rs::clap::{Arg, ArgMatches, Command};
+use daemonize::{Error, Result};
+use reedline_repl_rs::clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use reedline_repl_rs::Repl;
-use supervisor::Action;
+use supervisor::{Action, Output, ParseActionError, Payload, Request, Response, Signal};
 
 use crate::ClientContext;
 
-fn send_action(action: Action, ctx: &mut ClientContext) -> Result<Option<String>> {
-    ctx.writer
-        .write_all(format!("{}\n", action.to_string()).as_bytes())?;
+/// Set by ctrl-c while following the output of a process
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Send the action as a json request, return its id
+fn write_request(action: &Action, ctx: &mut ClientContext) -> Result<u64> {
+    ctx.request_id += 1;
+    let request = Request::new(ctx.request_id, action);
+    ctx.writer.write_all(request.encode()?.as_bytes())?;
     ctx.writer.flush()?;
-    if action == Action::Quit {
+    Ok(request.id)
+}
+
+/// Wait for the next response, which has to answer the request `id`
+fn read_response(id: u64, ctx: &mut ClientContext) -> Result<Response> {
+    let mut buf = String::new();
+    if ctx.reader.read_line(&mut buf)? == 0 {
+        return Err(Error::SupervisorError(
+            "Connection closed by the server".to_string(),
+        ));
+    }
+    let response = Response::decode(&buf)?;
+    if response.id != id {
+        return Err(Error::SupervisorError(format!(
+            "Expected a response to request {}, got {}",
+            id, response.id
+        )));
+    }
+    Ok(response)
+}
+
+/// Send the action as a json request and wait for the matching response
+fn send_request(action: Action, ctx: &mut ClientContext) -> Result<Response> {
+    let id = write_request(&action, ctx)?;
+    read_response(id, ctx)
+}
+
+fn print_output(response: &Response) {
+    if let Some(Payload::Output(output)) = &response.payload {
+        print!("{output}");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Print the output streamed for `tail -f` until ctrl-c, the server stops
+/// streaming at the next request. Return the response that ended it
+fn follow(action: Action, ctx: &mut ClientContext) -> Result<Response> {
+    let id = write_request(&action, ctx)?;
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    let previous =
+        unsafe { libc::signal(libc::SIGINT, interrupt as *const () as libc::sighandler_t) };
+
+    let mut stop = None;
+    let result = loop {
+        let response = match read_response(id, ctx) {
+            Ok(response) => response,
+            Err(e) => break Err(e),
+        };
+        print_output(&response);
+        if !response.more {
+            break Ok(response);
+        }
+        if stop.is_none() && INTERRUPTED.load(Ordering::SeqCst) {
+            stop = Some(write_request(&Action::Hello, ctx));
+        }
+    };
+    unsafe { libc::signal(libc::SIGINT, previous) };
+
+    match stop {
+        Some(hello) => {
+            read_response(hello?, ctx)?;
+            result
+        }
+        None => result,
+    }
+}
+
+/// Say hello to the server and authenticate with the token from
+/// `SERVER_AUTH_TOKEN_FILE` if it asks for it
+pub(crate) fn handshake(ctx: &mut ClientContext) -> Result<()> {
+    let hello = send_request(Action::Hello, ctx)?;
+    if !hello.is_ok() {
+        return Err(Error::SupervisorError(hello.to_string()));
+    }
+    let path = match std::env::var("SERVER_AUTH_TOKEN_FILE") {
+        Ok(path) => path,
+        Err(_) => return Ok(()),
+    };
+    let token = std::fs::read_to_string(&path)?.trim().to_string();
+    if token.is_empty() {
+        return Err(Error::SupervisorError(format!(
+            "The token file {path} is empty"
+        )));
+    }
+    let response = send_request(Action::Auth(token), ctx)?;
+    if !response.is_ok() {
+        return Err(Error::SupervisorError(response.to_string()));
+    }
+    Ok(())
+}
+
+fn send_action(action: Action, ctx: &mut ClientContext) -> Result<Option<String>> {
+    let quit = action == Action::Quit;
+    let response = send_request(action, ctx)?;
+    if quit {
         std::process::exit(0)
     }
+    Ok(Some(if response.is_ok() {
+        response.to_string()
+    } else {
+        format!("\x1B[31m{}\x1B[0m", response)
+    }))
+}
 
-    let mut buf = String::new();
-    ctx.reader.read_line(&mut buf)?;
+/// Exit codes of the one shot mode
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_UNREACHABLE: i32 = 3;
 
-    Ok(Some(format!("action : {:?}", buf)))
+/// Run a single command given on the command line, print the answer
+/// and return the exit code of the program
+pub(crate) fn one_shot(command: Vec<String>, connect: fn() -> Result<ClientContext>) -> i32 {
+    // the shell already split the words, they are kept as given
+    let parsed = match command.split_first() {
+        Some((action, args)) => Action::parse(action, args.to_vec()),
+        None => Err(ParseActionError::NoCommandFound),
+    };
+    let action = match parsed {
+        Ok(action) => action,
+        Err(e) => {
+            eprint!("{}", e);
+            return EXIT_USAGE;
+        }
+    };
+    let following = matches!(action, Action::Tail { follow: true, .. });
+    let response = connect().and_then(|mut ctx| {
+        handshake(&mut ctx)?;
+        if following {
+            follow(action, &mut ctx)
+        } else {
+            send_request(action, &mut ctx)
+        }
+    });
+    match response {
+        // the output has already been printed
+        Ok(response) if response.is_ok() && following => 0,
+        Ok(response) if response.is_ok() => {
+            println!("{}", response);
+            0
+        }
+        Ok(response) => {
+            eprintln!("{}", response);
+            EXIT_FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            EXIT_UNREACHABLE
+        }
+    }
 }
 
 fn quit(_args: ArgMatches, context: &mut ClientContext) -> Result<Option<String>> {
@@ -51,8 +202,39 @@ fn stop(args: ArgMatches, context: &mut ClientContext) -> Result<Option<String>>
         .unwrap_or_default();
     send_action(Action::Stop(programs), context)
 }
+fn signal(args: ArgMatches, context: &mut ClientContext) -> Result<Option<String>> {
+    let Some(signal) = args.get_one::<Signal>("signal").copied() else {
+        return Ok(None);
+    };
+    let programs = args
+        .get_many::<String>("programs")
+        .map(|v| v.cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    send_action(Action::Signal(signal, programs), context)
+}
+
+fn tail(args: ArgMatches, context: &mut ClientContext) -> Result<Option<String>> {
+    let action = Action::Tail {
+        target: args
+            .get_one::<String>("target")
+            .cloned()
+            .unwrap_or_default(),
+        output: match args.get_one::<String>("output").map(String::as_str) {
+            Some("stderr") => Output::Stderr,
+            _ => Output::Stdout,
+        },
+        lines: args.get_one::<usize>("lines").copied().unwrap_or(10),
+        follow: args.get_flag("follow"),
+    };
+    if !args.get_flag("follow") {
+        return send_action(action, context);
+    }
+    let response = follow(action, context)?;
+    Ok((!response.is_ok()).then(|| format!("\x1B[31m{}\x1B[0m", response)))
+}
 
-pub(crate) fn supervisor_ctl(ctx: ClientContext) -> Result<()> {
+pub(crate) fn supervisor_ctl(mut ctx: ClientContext) -> Result<()> {
+    handshake(&mut ctx)?;
     let mut repl = Repl::new(ctx)
         .with_name("Supervisor_ctl")
         .with_version("v0.1.0")
@@ -68,7 +250,7 @@ pub(crate) fn supervisor_ctl(ctx: ClientContext) -> Result<()> {
         .with_command(
             Command::new("restart")
                 .arg(Arg::new("programs").num_args(1..).required(true))
-                .about("Restart the given list of programs"),
+                .about("Restart the given list of programs, `group:*` or `group:name` for groups"),
             restart,
         )
         .with_command(
@@ -78,14 +260,50 @@ pub(crate) fn supervisor_ctl(ctx: ClientContext) -> Result<()> {
         .with_command(
             Command::new("start")
                 .arg(Arg::new("programs").num_args(1..).required(true))
-                .about("Start the given list of programs"),
+                .about("Start the given list of programs, `group:*` or `group:name` for groups"),
             start,
         )
         .with_command(
             Command::new("stop")
                 .arg(Arg::new("programs").num_args(1..).required(true))
-                .about("Stop the given list of programs"),
+                .about("Stop the given list of programs, `group:*` or `group:name` for groups"),
             stop,
+        )
+        .with_command(
+            Command::new("signal")
+                .arg(
+                    Arg::new("signal")
+                        .required(true)
+                        .value_parser(|s: &str| s.parse::<Signal>())
+                        .help("Name like HUP or SIGHUP, or number"),
+                )
+                .arg(Arg::new("programs").num_args(1..).required(true))
+                .about("Send a signal to the running processes of the given programs"),
+            signal,
+        )
+        .with_command(
+            Command::new("tail")
+                .arg(
+                    Arg::new("follow")
+                        .short('f')
+                        .action(ArgAction::SetTrue)
+                        .help("Keep printing the new output until ctrl-c"),
+                )
+                .arg(
+                    Arg::new("lines")
+                        .short('n')
+                        .value_parser(value_parser!(usize))
+                        .default_value("10")
+                        .help("Number of lines to print"),
+                )
+                .arg(Arg::new("target").required(true).help("program[:index]"))
+                .arg(
+                    Arg::new("output")
+                        .value_parser(["stdout", "stderr"])
+                        .default_value("stdout"),
+                )
+                .about("Print the last lines of the output of a process"),
+            tail,
         );
     Ok(repl.run()?)
 }